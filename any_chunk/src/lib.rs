@@ -25,16 +25,13 @@ pub fn chunk_it_up(input: TokenStream) -> TokenStream {
         }
     });
 
-    if chunkable_fields.len() > 1 {
-        panic!("chunkable attribute is only supported on one field at a time")
-    }
-
-    let chunk_field = chunkable_fields.first().unwrap();
-    let cf_name = &chunk_field.ident;
+    let chunk_fields = chunkable_fields.iter().map(|f| {
+        let name = &f.ident;
 
-    let chunk_field = quote! {
-        #cf_name
-    };
+        quote! {
+            #name
+        }
+    });
 
     let expanded = quote! {
         impl #struct_name {
@@ -45,7 +42,7 @@ pub fn chunk_it_up(input: TokenStream) -> TokenStream {
                 for i in 0..chunk_size {
                     foo.push(#struct_name {
                         #(#unchunk_fields: self.#unchunk_fields.clone(),)*
-                        #chunk_field: self.#chunk_field.clone()
+                        #(#chunk_fields: self.#chunk_fields.clone(),)*
                     })
                 }
                 foo
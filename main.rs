@@ -1,12 +1,20 @@
 use promptize::Promptize;
 
 
+#[derive(serde::Deserialize, schemars::JsonSchema, Debug)]
+pub struct FileSummary {
+    pub summary: String,
+    pub items: Vec<String>
+}
+
+
 #[derive(Promptize, Debug, serde::Serialize)]
+#[promptize(response = FileSummary)]
 pub struct FileContent {
     system_prompt: String,
     user_prompt: String,
     pub filename: String,
-    #[chunkable]
+    #[chunkable(split = "rust_items")]
     pub file_content: String
 }
 
@@ -15,16 +23,20 @@ fn main() {
     let contents = std::fs::read_to_string("/home/dan/documents/apps/temp/proc-macro-workshop/test_files/huge_file.rs").unwrap();
     let contents = contents
         .lines()
-        .map(|l| l.trim())
+        .map(|l| format!("{}\n", l.trim()))
         .collect::<String>();
 
-    let foo = FileContent::builder()
-        .system_prompt(format!("You are a computer system that responds only in JSON format with no other words except for the JSON."))
-        .user_prompt(format!("You are a computer system that responds only in JSON format with no other words except for the JSON."))
+    let mut builder = FileContent::builder();
+    builder
+        .system_prompt("You are a computer system that summarizes rust files.".to_string())
+        .user_prompt("Summarize this file and list the items in it.".to_string())
         .filename("huge_file.rs".to_string())
-        .file_content(contents)
-        .build_prompt("gpt-4", 8192, 4000)
-        .unwrap();
+        .file_content(contents);
+
+    // what it would take, before anything is built for real
+    println!("{}", builder.estimate("gpt-4", 1024).unwrap());
+
+    let prompts = builder.build_prompt_for("gpt-4", 1024).unwrap();
 
-    println!("{:#?}", foo);
+    println!("{:#?}", prompts);
 }
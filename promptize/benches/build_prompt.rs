@@ -0,0 +1,72 @@
+// Promptizes a batch of files the way a batch job would, once building a
+// fresh tiktoken encoder for every file like build_prompt used to, and once
+// with the encoders TiktokenTokenizer::for_model shares between calls.
+//
+//     cargo bench -p promptize
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use promptize::{Promptize, TiktokenTokenizer};
+
+#[derive(Promptize)]
+pub struct FileContent {
+    pub system_prompt: String,
+    pub user_prompt: String,
+    pub filename: String,
+    #[chunkable(split = "lines")]
+    pub file_content: String
+}
+
+const FILES: usize = 100;
+
+fn files() -> Vec<(String, String)> {
+    (0..FILES)
+        .map(|i| {
+            let content = (0..40)
+                .map(|line| format!("line {} of file {} says something about item {}\n", line, i, line * i))
+                .collect::<String>();
+
+            (format!("file_{}.txt", i), content)
+        })
+        .collect()
+}
+
+fn promptize(tokenizer: &TiktokenTokenizer, filename: &str, content: &str) {
+    FileContent::builder()
+        .system_prompt("You are a computer system that responds only in JSON.".to_string())
+        .user_prompt("Summarize this file.".to_string())
+        .filename(filename.to_string())
+        .file_content(content.to_string())
+        .build_prompt_with(tokenizer, 8192, 4000)
+        .unwrap();
+}
+
+fn build_prompt(c: &mut Criterion) {
+    let files = files();
+
+    let mut group = c.benchmark_group("build_prompt");
+    group.sample_size(10);
+    group.throughput(Throughput::Elements(FILES as u64));
+
+    group.bench_function(BenchmarkId::new("encoder", "per file"), |b| {
+        b.iter(|| {
+            for (filename, content) in files.iter() {
+                let tokenizer = TiktokenTokenizer::from(tiktoken_rs::get_bpe_from_model("gpt-4").unwrap());
+                promptize(&tokenizer, filename, content);
+            }
+        })
+    });
+
+    group.bench_function(BenchmarkId::new("encoder", "shared"), |b| {
+        b.iter(|| {
+            for (filename, content) in files.iter() {
+                let tokenizer = TiktokenTokenizer::for_model("gpt-4").unwrap();
+                promptize(&tokenizer, filename, content);
+            }
+        })
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, build_prompt);
+criterion_main!(benches);
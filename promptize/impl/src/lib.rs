@@ -0,0 +1,1582 @@
+use proc_macro::TokenStream;
+use quote::quote;
+use syn::{
+    parse_macro_input, DeriveInput
+};
+
+
+/// Allows fields of a struct to be marked as Chunkable as to denote that they are able to be
+/// broken up into chunks when being fed to an LLM to fit inside of a context window.
+#[proc_macro_derive(Promptize, attributes(chunkable, history, promptize, role, truncatable))]
+pub fn promptize(input: TokenStream) -> TokenStream {
+    let input_ast = parse_macro_input!(input as DeriveInput);
+
+    let name = &input_ast.ident;
+    let builder_name = format!("{}Builder", name); 
+    let builder_ident = syn::Ident::new(&builder_name, name.span());
+
+    let fields = if let syn::Data::Struct(syn::DataStruct { 
+        fields: syn::Fields::Named(syn::FieldsNamed { 
+            ref named, 
+            ..
+        }), 
+        ..
+    }) = input_ast.data {
+        named 
+    } else {
+        panic!("Only implemented for Struct");
+    };
+
+    let (chunkable_fields, _unchunkable_fields) = split_fields(fields.clone()); 
+
+    if chunkable_fields.is_empty() {
+        let error = syn::Error::new(name.span(), "at least one field has to be marked #[chunkable]");
+        return error.to_compile_error().into();
+    }
+
+    let struct_options = match struct_options(&input_ast.attrs) {
+        Ok(options) => options,
+        Err(error) => return error.to_compile_error().into()
+    };
+    let layout = &struct_options.layout;
+    let reduce_prompt = &struct_options.reduce_prompt;
+
+    let mut chunk_kinds = vec![];
+    let mut chunkable_options_list = vec![];
+    for chunk_field in chunkable_fields.iter() {
+        let kind = match chunk_kind(&chunk_field.ty) {
+            Ok(kind) => kind,
+            Err(error) => return error.to_compile_error().into()
+        };
+
+        match chunkable_options(chunk_field, &kind) {
+            Ok(options) => chunkable_options_list.push(options),
+            Err(error) => return error.to_compile_error().into()
+        }
+
+        chunk_kinds.push(kind);
+    }
+
+    let chunk_fields = chunkable_fields.iter().map(|f| f.ident.clone().unwrap()).collect::<Vec<_>>();
+    let chunk_field_indexes = (0..chunk_fields.len()).collect::<Vec<_>>();
+    let chunk_field_count = chunk_fields.len();
+    let overlaps = chunkable_options_list.iter().map(|options| options.overlap);
+    let splits = chunkable_options_list.iter().map(|options| {
+        let split = variant(&options.split);
+        quote! { ::promptize::SplitStrategy::#split }
+    });
+    let weights = chunkable_options_list.iter().map(|options| options.weight);
+    let priorities = chunkable_options_list.iter().map(|options| options.priority);
+
+    // text is measured as it is, collections as the json they are sent as
+    let field_tokens = chunk_fields.iter().zip(&chunk_kinds).map(|(name, kind)| {
+        let value = quote! {
+            self.#name
+                .as_ref()
+                .ok_or(::promptize::PromptizeError::MissingField(stringify!(#name)))?
+        };
+
+        match kind {
+            ChunkKind::Elements => quote! { ::promptize::__private::get_prompt_tokens(tokenizer, &serde_json::to_string(#value)?)? },
+            _ => quote! { ::promptize::__private::get_prompt_tokens(tokenizer, std::convert::AsRef::<str>::as_ref(#value))? }
+        }
+    });
+
+    let chunk_field_ranges = chunk_fields.iter().zip(&chunk_kinds).zip(&chunk_field_indexes).map(|((name, kind), index)| {
+        let chunk = match kind {
+            ChunkKind::Elements => quote! {
+                let element_tokens = value
+                    .iter()
+                    .map(|element| ::promptize::__private::get_prompt_tokens(tokenizer, &serde_json::to_string(element)?))
+                    .collect::<Result<std::vec::Vec<i32>, ::promptize::PromptizeError>>()?;
+
+                ::promptize::__private::chunk_elements(&element_tokens, chunk_size_tokens as usize, overlaps[#index], overflow)?
+            },
+            _ => quote! {
+                let text = std::convert::AsRef::<str>::as_ref(value);
+                ::promptize::__private::chunk_string(tokenizer, text, chunk_size_tokens as usize, overlaps[#index], splits[#index], overflow)?
+            }
+        };
+
+        quote! {
+            let chunks = match #layout {
+                "zip" if num_chunks[#index] > 1 => zipped_chunks,
+                _ => num_chunks[#index]
+            };
+            let chunk_size_tokens = ::promptize::__private::get_chunk_size_tokens(chunkable_field_tokens[#index], chunks, overlaps[#index] as i32);
+
+            // escaping the slice into json can cost more tokens than the raw text did, so
+            // every slice is measured in the request it ends up in, against the share of
+            // the budget its field was given
+            let overflow = |range: std::ops::Range<usize>| -> Result<usize, ::promptize::PromptizeError> {
+                let mut ranges = empty_ranges.clone();
+                ranges[#index] = range;
+                let chunk_tokens = tokenizer.count_messages(&chat(user_message(&ranges)?))? as i32 - unchunkable_tokens;
+                Ok((chunk_tokens - allocations[#index]).max(0) as usize)
+            };
+
+            let value = self.#name.as_ref().unwrap();
+            let ranges = { #chunk };
+
+            field_chunks.push(match ranges.is_empty() {
+                true => vec![0..0],
+                false => ranges
+            });
+        }
+    });
+
+    // each chunk is a range of the field, of bytes for text and of elements for collections
+    let chunk_slices = chunk_kinds.iter().map(|kind| match kind {
+        ChunkKind::String => quote! { value[range].to_string() },
+        ChunkKind::Str => quote! { &value[range] },
+        ChunkKind::Cow => quote! { std::borrow::Cow::Owned(value[range].to_string()) },
+        ChunkKind::Elements => quote! { value[range].to_vec() }
+    });
+
+    // the whole of each field, for when a prompt goes out in one piece
+    let whole_ranges = chunk_kinds.iter().map(|kind| match kind {
+        ChunkKind::Elements => quote! { 0..value.len() },
+        _ => quote! { 0..std::convert::AsRef::<str>::as_ref(value).len() }
+    });
+
+    let chunk_ranges = chunk_kinds.iter().map(|kind| match kind {
+        ChunkKind::Elements => quote! { ::promptize::ChunkRange::Elements(range) },
+        _ => quote! { ::promptize::ChunkRange::text(std::convert::AsRef::<str>::as_ref(value), range) }
+    });
+
+    // every field gets a setter of its own name, which can't share it with a method of the builder
+    if let Some(field) = fields.iter().find(|f| f.ident.as_ref().is_some_and(|ident| BUILDER_METHODS.iter().any(|m| ident == m))) {
+        let message = format!("a field can't be named {}, the builder has a method of that name", field.ident.as_ref().unwrap());
+        return syn::Error::new_spanned(&field.ident, message).to_compile_error().into();
+    }
+
+    let mut field_options_list = vec![];
+    for f in fields.iter() {
+        match field_options(f) {
+            Ok(options) => field_options_list.push(options),
+            Err(error) => return error.to_compile_error().into()
+        }
+    }
+
+    fn is_optional(field: &syn::Field) -> bool {
+        if let syn::Type::Path(t_path) = &field.ty {
+            let segments = &t_path.path.segments;
+            segments.len() == 1 && segments[0].ident == "Option"
+        } else {
+            false
+        }
+    }
+
+    let template_fields = fields.iter().zip(&field_options_list).map(|(f, options)| {
+        let name = &f.ident;
+        let ty = &f.ty;
+
+        // fields with a role and the history are sent as messages of their own, not as part of the json
+        let skip = (options.role.is_some() || options.history.is_some()).then(|| quote! { #[serde(skip)] });
+
+        if is_optional(f) {
+            return quote! {
+                #skip
+                #name: #ty
+            };
+        }
+
+        quote! {
+            #skip
+            #name: std::option::Option<#ty>
+        }
+    });
+
+    let role_messages = fields.iter().zip(&field_options_list).filter_map(|(f, options)| {
+        let name = &f.ident;
+        let role = variant(options.role.as_ref()?);
+
+        Some(quote! {
+            if let Some(content) = &self.#name {
+                messages.push(::promptize::Role::#role.message(content.to_string()));
+            }
+        })
+    });
+
+    let fields_empty = fields.iter().zip(&field_options_list).map(|(f, options)| {
+        let name = &f.ident;
+
+        // a conversation starts out without any turns
+        if options.history.is_some() {
+            return quote! {
+                #name: Some(std::default::Default::default())
+            };
+        }
+
+        match &options.default {
+            Some(default) => {
+                let ty = if is_optional(f) {
+                    let option_type = get_option_type(f);
+                    quote! { #option_type }
+                } else {
+                    let ty = &f.ty;
+                    quote! { #ty }
+                };
+
+                quote! {
+                    #name: Some(<#ty as std::convert::From<&str>>::from(#default))
+                }
+            },
+            None => quote! {
+                #name: None
+            }
+        }
+    });
+
+    // everything that is not an Option has to be set, or defaulted, before a prompt can be built
+    let required_checks = fields.iter().filter(|f| !is_optional(f)).map(|f| {
+        let name = &f.ident;
+        quote! {
+            if self.#name.is_none() {
+                return Err(::promptize::PromptizeError::MissingField(stringify!(#name)));
+            }
+        }
+    });
+
+    let field_names = fields.iter().filter_map(|f| f.ident.clone()).collect::<Vec<_>>();
+    let unchunkable_field_names = field_names.iter().filter(|name| !chunk_fields.contains(name)).collect::<Vec<_>>();
+
+    let mut history_fields = fields.iter().zip(&field_options_list).filter(|(_, options)| options.history.is_some());
+    let history_field = history_fields.next();
+    if let Some((field, _)) = history_fields.next() {
+        return syn::Error::new_spanned(field, "only one field can be marked #[history]").to_compile_error().into();
+    }
+
+    // the history goes in between the system messages and the rest, and in a chunked prompt gets a
+    // share of the budget of the chunkable fields, which it is left out of until it has it
+    let (history_messages, history_methods, history_reset, allocate) = match history_field {
+        Some((field, options)) => {
+            let name = &field.ident;
+            let HistoryOptions { weight, priority, summarize } = options.history.as_ref().unwrap();
+            let summarize = match summarize {
+                Some(summarize) => quote! {
+                    Some(#summarize as fn(&[::promptize::ChatCompletionRequestMessage]) -> ::promptize::ChatCompletionRequestMessage)
+                },
+                None => quote! { None }
+            };
+
+            let history_messages = quote! {
+                if let Some(history) = &self.#name {
+                    let at = messages.iter().take_while(|message| message.role == "system").count();
+                    messages.splice(at..at, history.iter().cloned());
+                }
+            };
+
+            let history_methods = quote! {
+                /// A copy of the builder with other turns of history
+                fn __promptize_with_history(&self, history: std::vec::Vec<::promptize::ChatCompletionRequestMessage>) -> Self {
+                    let mut prompt = self.clone();
+                    prompt.#name = Some(history);
+                    prompt
+                }
+            };
+
+            let history_reset = quote! {
+                let role_messages = self.__promptize_with_history(vec![]).__promptize_role_messages();
+                let chat = |user_message: String| {
+                    let mut prompt = role_messages.clone();
+                    prompt.push(::promptize::Role::User.message(user_message));
+                    prompt
+                };
+            };
+
+            let allocate = quote! {
+                // the history can have what the chunkable fields don't need, but never the tokens
+                // they were promised
+                let history_tokens = tokenizer.count_messages(&self.__promptize_role_messages())? as i32
+                    - tokenizer.count_messages(&role_messages)? as i32;
+                let history_tokens = std::cmp::min(history_tokens, chunkable_tokens_remaining - chunkable_tokens_required);
+
+                let mut allocations = ::promptize::__private::allocate_budget(
+                    budget,
+                    &[&chunkable_field_tokens[..], &[history_tokens]].concat(),
+                    &[&weights[..], &[#weight]].concat(),
+                    &[&priorities[..], &[#priority]].concat()
+                );
+                let history_allocation = allocations.pop().unwrap_or(0);
+
+                let history = self.#name.as_deref().unwrap_or_default();
+                let history = ::promptize::__private::fit_history(tokenizer, history, history_allocation, #summarize)?;
+                let role_messages = self.__promptize_with_history(history).__promptize_role_messages();
+                let chat = |user_message: String| {
+                    let mut prompt = role_messages.clone();
+                    prompt.push(::promptize::Role::User.message(user_message));
+                    prompt
+                };
+                let unchunkable_tokens = tokenizer.count_messages(&chat(user_message(&empty_ranges)?))? as i32;
+            };
+
+            (Some(history_messages), Some(history_methods), Some(history_reset), allocate)
+        },
+        None => (None, None, None, quote! {
+            let allocations = ::promptize::__private::allocate_budget(budget, &chunkable_field_tokens, &weights, &priorities);
+        })
+    };
+
+    // truncatable fields are cut lowest priority first, fields of the same priority in the order
+    // they are declared
+    let mut truncatable = fields
+        .iter()
+        .zip(&field_options_list)
+        .filter_map(|(f, options)| Some((f.ident.clone().unwrap(), options.truncatable.as_ref()?)))
+        .collect::<Vec<_>>();
+    truncatable.sort_by_key(|(_, options)| options.priority);
+
+    let truncatable_fields = truncatable.iter().map(|(name, _)| name).collect::<Vec<_>>();
+    let truncatable_field_indexes = 0..truncatable_fields.len();
+    let truncatable_field_count = truncatable_fields.len();
+    let ellipses = truncatable.iter().map(|(_, options)| variant(&options.ellipsis));
+
+    let (truncate, truncated_method) = match truncatable_fields.is_empty() {
+        true => (
+            quote! {
+                let (prompt, truncations): (&Self, std::vec::Vec<::promptize::Truncation>) = (self, vec![]);
+            },
+            None
+        ),
+        false => (
+            quote! {
+                let (truncated, truncations) = self.__promptize_truncated(tokenizer, &cuts)?;
+                let prompt = &truncated;
+            },
+            Some(quote! {
+                /// A copy of the builder with as many tokens cut from each truncatable field, in the order
+                /// they are cut in, as cuts says, and what was cut. Cuts are made from the whole text of
+                /// the fields, so that cutting more never puts an ellipsis in an ellipsis.
+                fn __promptize_truncated<T: ::promptize::Tokenizer>(
+                    &self,
+                    tokenizer: &T,
+                    cuts: &[usize]
+                ) -> Result<(Self, std::vec::Vec<::promptize::Truncation>), ::promptize::PromptizeError> {
+                    let mut prompt = self.clone();
+                    let mut truncations = vec![];
+                    #(
+                        if let (Some(text), 1..) = (&self.#truncatable_fields, cuts[#truncatable_field_indexes]) {
+                            let tokens_before = tokenizer.count(text)?;
+                            let tokens = tokens_before.saturating_sub(cuts[#truncatable_field_indexes]);
+                            let (text, removed) = ::promptize::truncate_text(text, tokenizer, tokens, ::promptize::Ellipsis::#ellipses)?;
+
+                            truncations.push(::promptize::Truncation {
+                                field: stringify!(#truncatable_fields),
+                                removed,
+                                tokens_before,
+                                tokens_after: tokenizer.count(&text)?
+                            });
+                            prompt.#truncatable_fields = Some(text);
+                        }
+                    )*
+                    Ok((prompt, truncations))
+                }
+            })
+        )
+    };
+
+    // without a template the fields without a role are sent as json
+    let render = match &struct_options.template {
+        Some(template) => {
+            let parts = match parse_template(template, &field_names, &chunk_fields) {
+                Ok(parts) => parts,
+                Err(error) => return error.to_compile_error().into()
+            };
+
+            let parts = parts.iter().map(|part| match part {
+                TemplatePart::Text(text) => quote! {
+                    rendered.push_str(#text);
+                },
+                TemplatePart::Field(field) => quote! {
+                    rendered.push_str(&::promptize::__private::template_value(&self.#field)?);
+                }
+            });
+
+            // a template file is read by the derive, this makes sure editing it rebuilds the crate
+            let track_file = struct_options.template_file.as_ref().map(|path| quote! {
+                const _: &str = include_str!(#path);
+            });
+
+            quote! {
+                #track_file
+                let mut rendered = String::new();
+                #(#parts)*
+                Ok(rendered)
+            }
+        },
+        None => quote! { Ok(serde_json::to_string(&self)?) }
+    };
+
+    // a header only goes in when the prompt is chunked, and takes its tokens out of the budget of
+    // the chunks along with the other fields
+    let (chunk_header, budget_header, user_message, chunk_message) = match &struct_options.annotate_chunks {
+        Some(header) => {
+            let header = header.clone().unwrap_or_else(|| {
+                let unit = match chunk_kinds[0] {
+                    ChunkKind::Elements => "items",
+                    _ => "lines"
+                };
+                syn::LitStr::new(&format!("Part {{part}} of {{parts}}, {} {{first}}-{{last}}", unit), name.span())
+            });
+
+            let mut header_names = field_names.clone();
+            header_names.extend(CHUNK_HEADER_VALUES.iter().map(|value| syn::Ident::new(value, header.span())));
+
+            let parts = match parse_template(&header, &header_names, &[]) {
+                Ok(parts) => parts,
+                Err(error) => return error.to_compile_error().into()
+            };
+
+            let parts = parts.iter().map(|part| match part {
+                TemplatePart::Text(text) => quote! {
+                    header.push_str(#text);
+                },
+                TemplatePart::Field(value) if CHUNK_HEADER_VALUES.iter().any(|v| value == v) => quote! {
+                    header.push_str(&#value.to_string());
+                },
+                TemplatePart::Field(field) => quote! {
+                    header.push_str(&::promptize::__private::template_value(&self.#field)?);
+                }
+            });
+
+            let chunk_header = quote! {
+                /// The header of the part-th of parts chunks, the first chunkable field of which covers
+                /// first to last
+                fn __promptize_chunk_header(
+                    &self,
+                    part: usize,
+                    parts: usize,
+                    first: usize,
+                    last: usize
+                ) -> Result<String, ::promptize::PromptizeError> {
+                    let mut header = String::new();
+                    #(#parts)*
+                    Ok(header)
+                }
+
+                /// The first and last line, or element for a collection, of the first chunkable field
+                /// that the ranges cover, counting from 1
+                fn __promptize_chunk_span(&self, ranges: &[std::ops::Range<usize>]) -> (usize, usize) {
+                    match self.__promptize_chunk_sources(ranges).swap_remove(0).range {
+                        ::promptize::ChunkRange::Text { lines, .. } => (lines.start + 1, lines.end),
+                        ::promptize::ChunkRange::Elements(range) => (range.start + 1, range.end)
+                    }
+                }
+            };
+
+            // the numbers in a header aren't known until the prompt is cut up, so the chunks are
+            // sized to leave room for the widest header they could make
+            let budget_header = quote! {
+                let widest = |n: usize| 10usize.saturating_pow(n.max(1).ilog10() + 1) - 1;
+                let most_parts = match #layout {
+                    "zip" => chunkable_field_tokens.iter().copied().max().unwrap_or(1) as usize,
+                    _ => chunkable_field_tokens.iter().fold(1usize, |parts, &tokens| parts.saturating_mul(tokens.max(1) as usize))
+                };
+                let (_, most_last) = self.__promptize_chunk_span(&self.__promptize_whole_ranges());
+                let header = self.__promptize_chunk_header(widest(most_parts), widest(most_parts), widest(most_last), widest(most_last))?;
+            };
+
+            let user_message = quote! {
+                Ok(format!("{}\n\n{}", header, self.__promptize_with_chunks(ranges).__promptize_render()?))
+            };
+
+            let chunk_message = quote! {{
+                let (first, last) = self.__promptize_chunk_span(ranges);
+                let header = self.__promptize_chunk_header(index + 1, total, first, last)?;
+                format!("{}\n\n{}", header, self.__promptize_with_chunks(ranges).__promptize_render()?)
+            }};
+
+            (Some(chunk_header), Some(budget_header), user_message, chunk_message)
+        },
+        None => (
+            None,
+            None,
+            quote! { self.__promptize_with_chunks(ranges).__promptize_render() },
+            quote! { self.__promptize_with_chunks(ranges).__promptize_render()? }
+        )
+    };
+
+    // the schema of the response goes in the system message, which goes out with every request and
+    // is counted with the role messages
+    let (response_instructions, parse_response) = match &struct_options.response {
+        Some(response) => {
+            let response_instructions = quote! {
+                let instructions = ::promptize::response_instructions::<#response>();
+                match messages.iter_mut().find(|message| message.role == "system") {
+                    Some(system) => system.content = format!("{}\n\n{}", system.content, instructions),
+                    None => messages.insert(0, ::promptize::Role::System.message(instructions))
+                }
+            };
+
+            let parse_response = quote! {
+                /// Parses an answer to one of the prompts as the response type, from inside of a code
+                /// block if the model put it in one
+                pub fn parse_response(&self, response: &str) -> Result<#response, ::promptize::PromptizeError> {
+                    ::promptize::parse_response(response)
+                }
+            };
+
+            (Some(response_instructions), Some(parse_response))
+        },
+        None => (None, None)
+    };
+
+    let builder_methods = fields.iter().map(|f| {
+        let name = &f.ident;
+        let ty = &f.ty;
+
+        if is_optional(f) {
+            // extract root type
+            let option_type = get_option_type(f);
+            return quote! {
+                pub fn #name(&mut self, #name: #option_type) -> &mut Self {
+                    self.#name = Some(#name);
+                    self
+                }
+            };
+        }
+
+        quote! {
+            pub fn #name(&mut self, #name: #ty) -> &mut Self {
+                self.#name = Some(#name);
+                self
+            }
+        }
+    });
+
+    // the builder borrows whatever the struct borrows
+    let generics = &input_ast.generics;
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+    let expanded = quote! {
+        #[derive(serde::Serialize, Clone)]
+        struct #builder_ident #generics #where_clause {
+            #(#template_fields),*
+        }
+        
+        impl #impl_generics #builder_ident #ty_generics #where_clause {
+            #(#builder_methods)*
+
+            pub fn build_prompt(
+                &self, 
+                model: &str, 
+                token_limit: i32,
+                chunkable_token_limit: i32
+            ) -> Result<::promptize::PromptPlan, ::promptize::PromptizeError> {
+                // models in the registry know their chat format's overhead, anything else tiktoken
+                // knows is counted with OpenAI's
+                match ::promptize::model_info(model) {
+                    Ok(info) => self.build_prompt_with(&info, token_limit, chunkable_token_limit),
+                    Err(_) => {
+                        let tokenizer = ::promptize::TiktokenTokenizer::for_model(model)?;
+                        self.build_prompt_with(&tokenizer, token_limit, chunkable_token_limit)
+                    }
+                }
+            }
+
+            /// Builds the prompts for a model in the model registry, leaving reserve_for_completion
+            /// tokens of its context window for the reply. At least half of what is left for the
+            /// prompt has to be left for the chunkable fields once the other fields are in.
+            pub fn build_prompt_for(
+                &self,
+                model: &str,
+                reserve_for_completion: i32
+            ) -> Result<::promptize::PromptPlan, ::promptize::PromptizeError> {
+                let info = ::promptize::model_info(model)?;
+
+                let token_limit = info.context_window - reserve_for_completion;
+                if token_limit <= 0 {
+                    return Err(::promptize::PromptizeError::BudgetTooSmall {
+                        available: info.context_window,
+                        required: reserve_for_completion
+                    });
+                }
+
+                self.build_prompt_with(&info, token_limit, token_limit / 2)
+            }
+
+            /// Same as build_prompt, but counts and cuts tokens with any tokenizer, for models
+            /// tiktoken doesn't know
+            pub fn build_prompt_with<T: ::promptize::Tokenizer>(
+                &self,
+                tokenizer: &T,
+                token_limit: i32,
+                chunkable_token_limit: i32
+            ) -> Result<::promptize::PromptPlan, ::promptize::PromptizeError> {
+                let mut chunks = vec![];
+                let truncations = self.__promptize_for_each_prompt(tokenizer, token_limit, chunkable_token_limit, |chunk| chunks.push(chunk))?;
+
+                Ok(::promptize::PromptPlan { chunks, truncations })
+            }
+
+            /// Works out what build_prompt_for(model, reserve_for_completion) would send without
+            /// holding on to the messages: how many requests, the tokens each takes and what the
+            /// model's input_price puts them at.
+            ///
+            /// The counts are of the messages as they would be sent, so every request is still
+            /// built and counted one at a time. That takes as long as build_prompt_for does, only
+            /// the memory of the whole plan is saved.
+            pub fn estimate(
+                &self,
+                model: &str,
+                reserve_for_completion: i32
+            ) -> Result<::promptize::Estimate, ::promptize::PromptizeError> {
+                let info = ::promptize::model_info(model)?;
+
+                let token_limit = info.context_window - reserve_for_completion;
+                if token_limit <= 0 {
+                    return Err(::promptize::PromptizeError::BudgetTooSmall {
+                        available: info.context_window,
+                        required: reserve_for_completion
+                    });
+                }
+
+                let mut tokens_per_request = vec![];
+                self.__promptize_for_each_prompt(&info, token_limit, token_limit / 2, |chunk| tokens_per_request.push(chunk.tokens))?;
+
+                Ok(::promptize::Estimate::new(model, tokens_per_request, info.input_price))
+            }
+
+            /// Builds the requests of build_prompt_with one at a time and hands each to visit in
+            /// order, so that they don't all have to be held at once. Returns the truncatable fields
+            /// that had to be cut short for the chunks to fit.
+            fn __promptize_for_each_prompt<T, F>(
+                &self,
+                tokenizer: &T,
+                token_limit: i32,
+                chunkable_token_limit: i32,
+                mut visit: F
+            ) -> Result<std::vec::Vec<::promptize::Truncation>, ::promptize::PromptizeError>
+            where
+                T: ::promptize::Tokenizer,
+                F: FnMut(::promptize::PromptChunk)
+            {
+                let truncatable_tokens: [usize; #truncatable_field_count] = [
+                    #(self.#truncatable_fields.as_deref().map_or(Ok(0), |text| tokenizer.count(text))?),*
+                ];
+                let mut cuts = [0usize; #truncatable_field_count];
+
+                // truncatable fields are cut a little more every time the chunks still don't fit,
+                // lowest priority first, until they do or there is nothing left to cut
+                loop {
+                    #truncate
+
+                    let (available, required) = match prompt.__promptize_for_each_prompt_as_is(tokenizer, token_limit, chunkable_token_limit, &mut visit)? {
+                        Some(short) => short,
+                        None => return Ok(truncations)
+                    };
+
+                    if !::promptize::__private::add_cuts(&mut cuts, &truncatable_tokens, (required - available) as usize) {
+                        return Err(::promptize::PromptizeError::BudgetTooSmall { available, required });
+                    }
+                }
+            }
+
+            /// __promptize_for_each_prompt with the fields as they are. Returns the tokens available for the
+            /// chunkable fields and the tokens they require when there are too few, without building
+            /// any requests.
+            fn __promptize_for_each_prompt_as_is<T, F>(
+                &self,
+                tokenizer: &T,
+                token_limit: i32,
+                chunkable_token_limit: i32,
+                visit: &mut F
+            ) -> Result<std::option::Option<(i32, i32)>, ::promptize::PromptizeError>
+            where
+                T: ::promptize::Tokenizer,
+                F: FnMut(::promptize::PromptChunk)
+            {
+                #(#required_checks)*
+
+                // everything without a role goes to the model in a user message of its own, after
+                // the messages of the fields with a role
+                let role_messages = self.__promptize_role_messages();
+                let chat = |user_message: String| {
+                    let mut prompt = role_messages.clone();
+                    prompt.push(::promptize::Role::User.message(user_message));
+                    prompt
+                };
+
+                // counts go over the messages as they are sent, so the chat format's tokens around
+                // every message are counted along with their content
+                let prompt = chat(self.__promptize_render()?);
+                let total_prompt_tokens = tokenizer.count_messages(&prompt)? as i32;
+
+                let combinations = if total_prompt_tokens > token_limit {
+                    let overlaps: [usize; #chunk_field_count] = [#(#overlaps),*];
+                    let splits: [::promptize::SplitStrategy; #chunk_field_count] = [#(#splits),*];
+                    let weights: [i32; #chunk_field_count] = [#(#weights),*];
+                    let priorities: [i32; #chunk_field_count] = [#(#priorities),*];
+
+                    let chunkable_field_tokens: [i32; #chunk_field_count] = [#(#field_tokens),*];
+
+                    // the role messages go out with every chunk, and so do the non chunkable
+                    // fields since every user message is the whole builder with only a slice of
+                    // each chunkable field in it
+                    #history_reset
+                    #budget_header
+                    let user_message = |ranges: &[std::ops::Range<usize>]| -> Result<String, ::promptize::PromptizeError> {
+                        #user_message
+                    };
+                    let empty_ranges = vec![0..0; #chunk_field_count];
+                    let unchunkable_tokens = tokenizer.count_messages(&chat(user_message(&empty_ranges)?))? as i32;
+
+                    // this represents the tokens left after non chunkable fields are removed
+                    // since non chunkable fields cannot be changed, this is our "real" limit
+                    let chunkable_tokens_remaining = token_limit - unchunkable_tokens;
+
+                    // 8000 - (10000 - 8000) = 6000
+                    // 8000 - (10000 - 1000) = -1000
+                    // 8000 - (10000 - 3000) = 1000
+
+                    // we need to set a reasonable limit for "real_token_limit"
+                    // ex: if we only have 1000 tokens but the chunkable field is 20000
+                    // we don't want to call the API 20 times. Fields that are smaller than that
+                    // only need room for themselves, a token each at least and the spares below,
+                    // so that truncatable fields aren't cut any shorter than it takes
+                    let chunkable_tokens_required = std::cmp::min(
+                        chunkable_token_limit,
+                        chunkable_field_tokens.iter().map(|&tokens| tokens.max(1)).sum::<i32>() + (#chunk_field_count as i32 - 1)
+                    );
+                    if chunkable_tokens_remaining < chunkable_tokens_required {
+                        return Ok(Some((chunkable_tokens_remaining, chunkable_tokens_required)));
+                    }
+
+                    // each chunkable field is measured on its own, keep a token spare for every
+                    // place two of them meet in the same message and could tokenize differently
+                    let budget = chunkable_tokens_remaining - (#chunk_field_count as i32 - 1);
+                    #allocate
+
+                    // every chunk needs room for the overlap and at least one token past it, fields
+                    // that fit in their share go out whole and have no chunks to overlap
+                    for ((total, allocation), overlap) in chunkable_field_tokens.iter().zip(&allocations).zip(overlaps) {
+                        if total > allocation && overlap as i32 >= *allocation {
+                            return Err(::promptize::PromptizeError::BudgetTooSmall {
+                                available: *allocation,
+                                required: overlap as i32 + 1
+                            });
+                        }
+                    }
+
+                    let num_chunks = chunkable_field_tokens
+                        .iter()
+                        .zip(&allocations)
+                        .zip(overlaps)
+                        .map(|((&total, &allocation), overlap)| ::promptize::__private::get_num_chunks(total, allocation, overlap as i32))
+                        .collect::<std::vec::Vec<i32>>();
+
+                    // zipped fields are all cut into the same number of chunks so that the nth
+                    // chunk of each covers roughly the same part of its field, fields that fit
+                    // in their share of the budget are left whole and go out with every chunk
+                    let zipped_chunks = num_chunks.iter().copied().max().unwrap_or(1);
+
+                    let mut field_chunks = vec![];
+                    #({ #chunk_field_ranges })*
+
+                    let combinations = match #layout {
+                        "zip" => ::promptize::__private::zip_chunks(&field_chunks),
+                        _ => ::promptize::__private::cartesian_chunks(&field_chunks)
+                    };
+
+                    // the history a chunked prompt goes out with is whatever of it fit
+                    Some((combinations, role_messages.clone()))
+                } else {
+                    None
+                };
+
+                // a prompt that fits goes out whole, as it was counted
+                let (combinations, role_messages, mut whole) = match combinations {
+                    Some((combinations, role_messages)) => (combinations, role_messages, None),
+                    None => (vec![self.__promptize_whole_ranges()], role_messages.clone(), Some(prompt))
+                };
+                let chat = |user_message: String| {
+                    let mut prompt = role_messages.clone();
+                    prompt.push(::promptize::Role::User.message(user_message));
+                    prompt
+                };
+
+                let total = combinations.len();
+                for (index, ranges) in combinations.iter().enumerate() {
+                    let messages = match whole.take() {
+                        Some(prompt) => prompt,
+                        None => chat(#chunk_message)
+                    };
+
+                    // chunks are sized from counts of the parts of a prompt, the prompts themselves
+                    // are what the API checks against the limit
+                    let tokens = tokenizer.count_messages(&messages)?;
+                    if tokens as i32 > token_limit {
+                        return Err(::promptize::PromptizeError::OverLimit { tokens: tokens as i32, limit: token_limit });
+                    }
+
+                    visit(::promptize::PromptChunk {
+                        messages,
+                        tokens,
+                        index,
+                        total,
+                        sources: self.__promptize_chunk_sources(ranges)
+                    });
+                }
+
+                Ok(None)
+            }
+
+            /// Builds the prompts that combine the answers to the prompts of a plan, one answer per
+            /// request, into one. Each request has the messages of the fields with a role and a user
+            /// message with the struct's reduce_prompt and as many of the answers, in order, as fit
+            /// in token_limit. When they don't all fit in one, the answers to these prompts have to
+            /// be combined again, see reduce.
+            pub fn build_reduce_prompt(
+                &self,
+                partial_answers: &[String],
+                model: &str,
+                token_limit: i32
+            ) -> Result<::promptize::PromptPlan, ::promptize::PromptizeError> {
+                match ::promptize::model_info(model) {
+                    Ok(info) => self.build_reduce_prompt_with(partial_answers, &info, token_limit),
+                    Err(_) => {
+                        let tokenizer = ::promptize::TiktokenTokenizer::for_model(model)?;
+                        self.build_reduce_prompt_with(partial_answers, &tokenizer, token_limit)
+                    }
+                }
+            }
+
+            /// Same as build_reduce_prompt, but counts and cuts tokens with any tokenizer
+            pub fn build_reduce_prompt_with<T: ::promptize::Tokenizer>(
+                &self,
+                partial_answers: &[String],
+                tokenizer: &T,
+                token_limit: i32
+            ) -> Result<::promptize::PromptPlan, ::promptize::PromptizeError> {
+                // answers are numbered across the whole plan so that their order survives being
+                // combined in groups
+                let parts = partial_answers
+                    .iter()
+                    .enumerate()
+                    .map(|(i, answer)| format!("\n\nPart {} of {}:\n{}", i + 1, partial_answers.len(), answer))
+                    .collect::<std::vec::Vec<String>>();
+
+                let role_messages = self.__promptize_role_messages();
+                let chat = |range: std::ops::Range<usize>| {
+                    let mut prompt = role_messages.clone();
+                    prompt.push(::promptize::Role::User.message(format!("{}{}", #reduce_prompt, parts[range].concat())));
+                    prompt
+                };
+
+                let budget = token_limit - tokenizer.count_messages(&chat(0..0))? as i32;
+                if budget <= 0 {
+                    return Err(::promptize::PromptizeError::BudgetTooSmall {
+                        available: budget,
+                        required: 1
+                    });
+                }
+
+                let part_tokens = parts
+                    .iter()
+                    .map(|part| ::promptize::__private::get_prompt_tokens(tokenizer, part))
+                    .collect::<Result<std::vec::Vec<i32>, ::promptize::PromptizeError>>()?;
+                let overflow = |range: std::ops::Range<usize>| -> Result<usize, ::promptize::PromptizeError> {
+                    Ok((tokenizer.count_messages(&chat(range))? as i32 - token_limit).max(0) as usize)
+                };
+                let ranges = ::promptize::__private::chunk_elements(&part_tokens, budget as usize, 0, overflow)?;
+
+                // combining answers one at a time never gets down to a single answer
+                if ranges.len() > 1 && ranges.len() == parts.len() {
+                    let required = part_tokens.windows(2).map(|pair| pair[0] + pair[1]).min().unwrap_or(0);
+                    return Err(::promptize::PromptizeError::BudgetTooSmall { available: budget, required });
+                }
+
+                let total = ranges.len();
+                let mut chunks = vec![];
+                for (index, range) in ranges.into_iter().enumerate() {
+                    let messages = chat(range.clone());
+                    chunks.push(::promptize::PromptChunk {
+                        tokens: tokenizer.count_messages(&messages)?,
+                        messages,
+                        index,
+                        total,
+                        sources: vec![::promptize::ChunkSource {
+                            field: "partial_answers",
+                            range: ::promptize::ChunkRange::Elements(range)
+                        }]
+                    });
+                }
+
+                Ok(::promptize::PromptPlan { chunks, truncations: vec![] })
+            }
+
+            /// Combines the answers to the prompts of a plan into one answer. complete sends a
+            /// request and returns the model's answer to it. Answers are combined in as few requests
+            /// as fit in token_limit, and the answers to those are combined again until there is
+            /// only one left.
+            pub fn reduce<F, E>(
+                &self,
+                partial_answers: std::vec::Vec<String>,
+                model: &str,
+                token_limit: i32,
+                mut complete: F
+            ) -> Result<String, E>
+            where
+                F: FnMut(&::promptize::PromptChunk) -> Result<String, E>,
+                E: std::convert::From<::promptize::PromptizeError>
+            {
+                let mut answers = partial_answers;
+                while answers.len() > 1 {
+                    let plan = self.build_reduce_prompt(&answers, model, token_limit)?;
+                    answers = plan.iter().map(&mut complete).collect::<Result<std::vec::Vec<String>, E>>()?;
+                }
+
+                Ok(answers.pop().unwrap_or_default())
+            }
+
+            /// The text of the last user message, the struct's template filled in with the fields or
+            /// the fields without a role as json when there is no template
+            fn __promptize_render(&self) -> Result<String, ::promptize::PromptizeError> {
+                #render
+            }
+
+            /// A copy of the builder with each chunkable field, in field order, cut down to a range of
+            /// itself. The whole of a chunkable field is never copied, this runs for every chunk that
+            /// is measured.
+            fn __promptize_with_chunks(&self, ranges: &[std::ops::Range<usize>]) -> Self {
+                Self {
+                    #(#unchunkable_field_names: self.#unchunkable_field_names.clone(),)*
+                    #(
+                        #chunk_fields: self.#chunk_fields.as_ref().map(|value| {
+                            let range = ranges[#chunk_field_indexes].clone();
+                            #chunk_slices
+                        }),
+                    )*
+                }
+            }
+
+            /// The range of each chunkable field that takes all of it
+            fn __promptize_whole_ranges(&self) -> std::vec::Vec<std::ops::Range<usize>> {
+                let mut ranges = vec![];
+                #(
+                    // required fields are checked to be set before there is anything to chunk
+                    let value = self.#chunk_fields.as_ref().unwrap();
+                    ranges.push(#whole_ranges);
+                )*
+                ranges
+            }
+
+            /// What the ranges of each chunkable field cover, by field
+            fn __promptize_chunk_sources(&self, ranges: &[std::ops::Range<usize>]) -> std::vec::Vec<::promptize::ChunkSource> {
+                let mut sources = vec![];
+                #(
+                    let value = self.#chunk_fields.as_ref().unwrap();
+                    let range = ranges[#chunk_field_indexes].clone();
+                    sources.push(::promptize::ChunkSource {
+                        field: stringify!(#chunk_fields),
+                        range: #chunk_ranges
+                    });
+                )*
+                sources
+            }
+
+            #chunk_header
+
+            #truncated_method
+
+            #parse_response
+
+            #history_methods
+
+            /// The fields that have a role as chat messages, in the order the fields are declared, the
+            /// schema of the response type in the system message if there is one and the history after
+            /// the system messages
+            fn __promptize_role_messages(&self) -> std::vec::Vec<::promptize::ChatCompletionRequestMessage> {
+                let mut messages: std::vec::Vec<::promptize::ChatCompletionRequestMessage> = vec![];
+                #(#role_messages)*
+                #response_instructions
+                #history_messages
+                messages
+            }
+        }
+
+        impl #impl_generics #name #ty_generics #where_clause {
+            fn builder() -> #builder_ident #ty_generics {
+                #builder_ident {
+                    #(#fields_empty,)*
+                }
+            }
+        }
+
+    };
+
+    proc_macro::TokenStream::from(expanded)
+}
+
+fn split_fields(fields: syn::punctuated::Punctuated<syn::Field, syn::token::Comma>) -> (Vec<syn::Field>, Vec<syn::Field>) {
+    let (chunkable, not): (Vec<_>, Vec<_>) = fields
+        .into_iter()
+        .partition(|field| {
+            field.attrs.iter().any(|attr| attr.path().is_ident("chunkable"))
+        });
+
+    (chunkable, not)
+}
+
+/// The public methods of the builder. Its private ones start with __promptize_ so that they can't
+/// clash with the setters of the fields.
+const BUILDER_METHODS: &[&str] = &[
+    "build_prompt",
+    "build_prompt_for",
+    "build_prompt_with",
+    "estimate",
+    "build_reduce_prompt",
+    "build_reduce_prompt_with",
+    "reduce",
+    "parse_response",
+];
+
+/// Ways of combining the chunks of several chunkable fields into requests, see
+/// #[promptize(layout = "...")]. "zip" sends the nth chunk of every field together and
+/// "cartesian" sends every combination of chunks.
+const LAYOUTS: &[&str] = &["zip", "cartesian"];
+
+/// What build_reduce_prompt asks of the model unless #[promptize(reduce_prompt = "...")] says
+/// otherwise
+const DEFAULT_REDUCE_PROMPT: &str = "These are answers to the same prompt, each for a different part of the input. \
+Combine them into a single answer to the prompt for the whole input.";
+
+/// Numbers a chunk header can have placeholders for besides the fields of the struct: which chunk
+/// it is, how many there are and the first and last line, or element for a collection, of the first
+/// chunkable field that the chunk covers. They take the place of fields with the same name.
+const CHUNK_HEADER_VALUES: &[&str] = &["part", "parts", "first", "last"];
+
+/// Options set on the struct through #[promptize(...)]
+struct StructOptions {
+    /// Template for the last user message, with {field} placeholders
+    template: Option<syn::LitStr>,
+    /// Path the template was read from, under the crate root or else next to the file of the struct
+    template_file: Option<syn::LitStr>,
+    /// One of LAYOUTS
+    layout: String,
+    /// Instruction the partial answers to a chunked prompt are sent with to be combined
+    reduce_prompt: String,
+    /// Header every chunk of a chunked prompt starts with, Some(None) for the default header
+    annotate_chunks: Option<Option<syn::LitStr>>,
+    /// Type the model is asked to answer with, as JSON
+    response: Option<syn::Type>,
+}
+
+impl Default for StructOptions {
+    fn default() -> Self {
+        StructOptions {
+            template: None,
+            template_file: None,
+            layout: "zip".to_string(),
+            reduce_prompt: DEFAULT_REDUCE_PROMPT.to_string(),
+            annotate_chunks: None,
+            response: None,
+        }
+    }
+}
+
+fn struct_options(attrs: &[syn::Attribute]) -> syn::Result<StructOptions> {
+    let mut options = StructOptions::default();
+
+    for attr in attrs.iter().filter(|attr| attr.path().is_ident("promptize")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("template") {
+                options.template = Some(meta.value()?.parse()?);
+                return Ok(());
+            }
+
+            if meta.path.is_ident("template_file") {
+                let path: syn::LitStr = meta.value()?.parse()?;
+                let manifest_dir = std::env::var("CARGO_MANIFEST_DIR").unwrap_or_default();
+                let mut file = std::path::Path::new(&manifest_dir).join(path.value());
+
+                // otherwise it's looked for next to the file the struct is in, like include_str! does
+                if !file.exists() {
+                    if let Some(dir) = path.span().local_file().as_ref().and_then(|source| source.parent()) {
+                        file = dir.join(path.value());
+                    }
+                }
+
+                let template = std::fs::read_to_string(&file)
+                    .map_err(|error| syn::Error::new(path.span(), format!("failed to read template: {}", error)))?;
+
+                options.template = Some(syn::LitStr::new(&template, path.span()));
+                options.template_file = Some(syn::LitStr::new(&file.to_string_lossy(), path.span()));
+                return Ok(());
+            }
+
+            if meta.path.is_ident("layout") {
+                let layout: syn::LitStr = meta.value()?.parse()?;
+                if !LAYOUTS.contains(&layout.value().as_str()) {
+                    let message = format!("layout must be one of {}", LAYOUTS.join(", "));
+                    return Err(syn::Error::new(layout.span(), message));
+                }
+
+                options.layout = layout.value();
+                return Ok(());
+            }
+
+            if meta.path.is_ident("annotate_chunks") {
+                options.annotate_chunks = match meta.input.peek(syn::Token![=]) {
+                    true => Some(Some(meta.value()?.parse()?)),
+                    false => Some(None)
+                };
+                return Ok(());
+            }
+
+            if meta.path.is_ident("response") {
+                options.response = Some(meta.value()?.parse()?);
+                return Ok(());
+            }
+
+            if meta.path.is_ident("reduce_prompt") {
+                let reduce_prompt: syn::LitStr = meta.value()?.parse()?;
+                options.reduce_prompt = reduce_prompt.value();
+                return Ok(());
+            }
+
+            Err(meta.error("unsupported promptize option"))
+        })?;
+    }
+
+    Ok(options)
+}
+
+/// A piece of a template, either text that is sent as is or a {field} placeholder
+enum TemplatePart {
+    Text(String),
+    Field(syn::Ident),
+}
+
+/// Splits a template into its parts, making sure every placeholder names a field and that there
+/// is a placeholder for each chunkable field to put its chunks in. {{ and }} escape a brace.
+fn parse_template(template: &syn::LitStr, fields: &[syn::Ident], chunk_fields: &[syn::Ident]) -> syn::Result<Vec<TemplatePart>> {
+    let source = template.value();
+    let mut parts = vec![];
+    let mut text = String::new();
+    let mut chars = source.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '{' if chars.peek() == Some(&'{') => {
+                chars.next();
+                text.push('{');
+            },
+            '}' if chars.peek() == Some(&'}') => {
+                chars.next();
+                text.push('}');
+            },
+            '{' => {
+                let mut placeholder = String::new();
+                loop {
+                    match chars.next() {
+                        Some('}') => break,
+                        Some(c) => placeholder.push(c),
+                        None => return Err(syn::Error::new(template.span(), "unmatched { in template, use {{ for a literal brace"))
+                    }
+                }
+                let placeholder = placeholder.trim();
+
+                let field = fields.iter().find(|field| *field == placeholder).ok_or_else(|| {
+                    let message = format!("template placeholder {{{}}} is not a field of the struct", placeholder);
+                    syn::Error::new(template.span(), message)
+                })?;
+
+                parts.push(TemplatePart::Text(std::mem::take(&mut text)));
+                parts.push(TemplatePart::Field(field.clone()));
+            },
+            '}' => return Err(syn::Error::new(template.span(), "unmatched } in template, use }} for a literal brace")),
+            c => text.push(c)
+        }
+    }
+
+    parts.push(TemplatePart::Text(text));
+
+    for chunk_field in chunk_fields {
+        let has_chunk_field = parts.iter().any(|part| matches!(part, TemplatePart::Field(field) if field == chunk_field));
+        if !has_chunk_field {
+            let message = format!("template needs a {{{}}} placeholder to put each chunk in", chunk_field);
+            return Err(syn::Error::new(template.span(), message));
+        }
+    }
+
+    Ok(parts)
+}
+
+/// Chat roles a field can be sent as through #[role(...)]
+const ROLES: &[&str] = &["system", "user", "assistant"];
+
+/// Where a #[truncatable] field can be cut short, see #[truncatable(ellipsis = "...")]. "tail" keeps
+/// the start of the text, "head" the end and "middle" both.
+const ELLIPSES: &[&str] = &["head", "tail", "middle"];
+
+/// Options set on a field through #[promptize(...)], #[role(...)], #[history(...)] and
+/// #[truncatable(...)]
+#[derive(Default)]
+struct FieldOptions {
+    /// Value the field starts out with on a new builder
+    default: Option<syn::LitStr>,
+    /// One of ROLES if the field is sent as a message of its own
+    role: Option<String>,
+    /// Set if the field holds the turns of a conversation so far
+    history: Option<HistoryOptions>,
+    /// Set if the field can be cut short to make room for the chunks
+    truncatable: Option<TruncatableOptions>,
+}
+
+/// Options set on a field through #[history(...)]. The history is budgeted along with the
+/// chunkable fields, by the same weight and priority rules, and only the most recent turns that
+/// fit in its share are sent.
+struct HistoryOptions {
+    weight: i32,
+    /// Below the chunkable fields by default, so history only gets what they don't need
+    priority: i32,
+    /// fn(&[ChatCompletionRequestMessage]) -> ChatCompletionRequestMessage that sums up the turns
+    /// that are dropped
+    summarize: Option<syn::Path>,
+}
+
+impl Default for HistoryOptions {
+    fn default() -> Self {
+        HistoryOptions {
+            weight: 1,
+            priority: -1,
+            summarize: None,
+        }
+    }
+}
+
+/// Options set on a field through #[truncatable(...)]. When the other fields leave too little of the
+/// budget for the chunkable ones, truncatable fields are cut down until they don't.
+struct TruncatableOptions {
+    /// Truncatable fields with a lower priority are cut before those with a higher one
+    priority: i32,
+    /// One of ELLIPSES
+    ellipsis: String,
+}
+
+impl Default for TruncatableOptions {
+    fn default() -> Self {
+        TruncatableOptions {
+            priority: 0,
+            ellipsis: "tail".to_string(),
+        }
+    }
+}
+
+fn field_options(field: &syn::Field) -> syn::Result<FieldOptions> {
+    let mut options = FieldOptions::default();
+
+    for attr in field.attrs.iter() {
+        if attr.path().is_ident("promptize") {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("default") {
+                    options.default = Some(meta.value()?.parse()?);
+                    return Ok(());
+                }
+
+                Err(meta.error("unsupported promptize option"))
+            })?;
+        }
+
+        if attr.path().is_ident("history") {
+            let history = options.history.get_or_insert_with(HistoryOptions::default);
+
+            // a bare #[history] keeps the defaults
+            if let syn::Meta::Path(_) = attr.meta {
+                continue;
+            }
+
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("weight") {
+                    let weight: syn::LitInt = meta.value()?.parse()?;
+                    history.weight = weight.base10_parse()?;
+                    if history.weight < 1 {
+                        return Err(syn::Error::new(weight.span(), "weight must be at least 1"));
+                    }
+                    return Ok(());
+                }
+
+                if meta.path.is_ident("priority") {
+                    let priority: syn::LitInt = meta.value()?.parse()?;
+                    history.priority = priority.base10_parse()?;
+                    return Ok(());
+                }
+
+                if meta.path.is_ident("summarize") {
+                    history.summarize = Some(meta.value()?.parse()?);
+                    return Ok(());
+                }
+
+                Err(meta.error("unsupported history option"))
+            })?;
+        }
+
+        if attr.path().is_ident("truncatable") {
+            let truncatable = options.truncatable.get_or_insert_with(TruncatableOptions::default);
+
+            // a bare #[truncatable] keeps the defaults
+            if let syn::Meta::Path(_) = attr.meta {
+                continue;
+            }
+
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("priority") {
+                    let priority: syn::LitInt = meta.value()?.parse()?;
+                    truncatable.priority = priority.base10_parse()?;
+                    return Ok(());
+                }
+
+                if meta.path.is_ident("ellipsis") {
+                    let ellipsis: syn::LitStr = meta.value()?.parse()?;
+                    if !ELLIPSES.contains(&ellipsis.value().as_str()) {
+                        let message = format!("ellipsis must be one of {}", ELLIPSES.join(", "));
+                        return Err(syn::Error::new(ellipsis.span(), message));
+                    }
+
+                    truncatable.ellipsis = ellipsis.value();
+                    return Ok(());
+                }
+
+                Err(meta.error("unsupported truncatable option"))
+            })?;
+        }
+
+        if attr.path().is_ident("role") {
+            attr.parse_nested_meta(|meta| {
+                match ROLES.iter().find(|role| meta.path.is_ident(role)) {
+                    Some(role) => {
+                        options.role = Some(role.to_string());
+                        Ok(())
+                    },
+                    None => Err(meta.error(format!("role must be one of {}", ROLES.join(", "))))
+                }
+            })?;
+        }
+    }
+
+    // structs from before #[role] existed keep working
+    if options.role.is_none() {
+        match field.ident.as_ref() {
+            Some(ident) if ident == "system_prompt" => options.role = Some("system".to_string()),
+            Some(ident) if ident == "user_prompt" => options.role = Some("user".to_string()),
+            _ => {}
+        }
+    }
+
+    // the chunks go in the user message, a field sent as a message of its own would be sent whole
+    if options.role.is_some() && field.attrs.iter().any(|attr| attr.path().is_ident("chunkable")) {
+        let message = match field.ident.as_ref() {
+            Some(ident) if ident == "system_prompt" || ident == "user_prompt" => {
+                format!("a chunkable field can't have a role, and {} is always sent as a message of its own", ident)
+            },
+            _ => "a chunkable field can't have a role".to_string()
+        };
+        return Err(syn::Error::new_spanned(field, message));
+    }
+
+    if options.history.is_some() {
+        if options.role.is_some() || field.attrs.iter().any(|attr| attr.path().is_ident("chunkable")) {
+            return Err(syn::Error::new_spanned(field, "a #[history] field can't be chunkable or have a role"));
+        }
+
+        let is_vec = match &field.ty {
+            syn::Type::Path(p) => p.path.segments.last().is_some_and(|segment| {
+                segment.ident == "Vec" || (segment.ident == "Option" && is_argument(&segment.arguments, "Vec"))
+            }),
+            _ => false
+        };
+        if !is_vec {
+            return Err(syn::Error::new_spanned(&field.ty, "history fields have to be a Vec<ChatCompletionRequestMessage>"));
+        }
+    }
+
+    if options.truncatable.is_some() {
+        if options.history.is_some() || field.attrs.iter().any(|attr| attr.path().is_ident("chunkable")) {
+            return Err(syn::Error::new_spanned(field, "a #[truncatable] field can't be chunkable or history"));
+        }
+
+        let is_string = match &field.ty {
+            syn::Type::Path(p) => p.path.segments.last().is_some_and(|segment| {
+                segment.ident == "String" || (segment.ident == "Option" && is_argument(&segment.arguments, "String"))
+            }),
+            _ => false
+        };
+        if !is_string {
+            return Err(syn::Error::new_spanned(&field.ty, "truncatable fields have to be a String"));
+        }
+    }
+
+    Ok(options)
+}
+
+/// Whether the type in the <> of a generic type, like an Option, is named name
+fn is_argument(arguments: &syn::PathArguments, name: &str) -> bool {
+    match arguments {
+        syn::PathArguments::AngleBracketed(args) => args.args.iter().any(|arg| {
+            matches!(arg, syn::GenericArgument::Type(syn::Type::Path(p)) if p.path.segments.last().is_some_and(|s| s.ident == name))
+        }),
+        _ => false
+    }
+}
+
+/// Ways of splitting a chunkable field, see #[chunkable(split = "...")]. Everything but "tokens"
+/// prefers cutting on the end of a line, paragraph, sentence or rust item over a bare token cut.
+const SPLIT_STRATEGIES: &[&str] = &["tokens", "lines", "paragraphs", "sentences", "rust_items"];
+
+/// The types a #[chunkable] field can have. Text is cut up on token boundaries, collections
+/// between whole elements.
+enum ChunkKind {
+    String,
+    Str,
+    Cow,
+    Elements,
+}
+
+fn chunk_kind(ty: &syn::Type) -> syn::Result<ChunkKind> {
+    let error = || syn::Error::new_spanned(ty, "chunkable fields have to be a String, &str, Cow<str> or Vec");
+
+    match ty {
+        syn::Type::Reference(reference) => match &*reference.elem {
+            syn::Type::Path(p) if p.path.is_ident("str") => Ok(ChunkKind::Str),
+            _ => Err(error())
+        },
+        syn::Type::Path(p) => {
+            let segment = p.path.segments.last().ok_or_else(error)?;
+
+            if segment.ident == "String" {
+                return Ok(ChunkKind::String);
+            }
+
+            if segment.ident == "Vec" {
+                return Ok(ChunkKind::Elements);
+            }
+
+            if segment.ident == "Cow" {
+                let borrows_str = match &segment.arguments {
+                    syn::PathArguments::AngleBracketed(args) => args.args.iter().any(|arg| {
+                        matches!(arg, syn::GenericArgument::Type(syn::Type::Path(p)) if p.path.is_ident("str"))
+                    }),
+                    _ => false
+                };
+
+                if borrows_str {
+                    return Ok(ChunkKind::Cow);
+                }
+            }
+
+            Err(error())
+        },
+        _ => Err(error())
+    }
+}
+
+/// Options set on a field through #[chunkable(...)]
+struct ChunkableOptions {
+    /// Number of tokens from the end of each chunk that are repeated at the start of the next
+    overlap: usize,
+    /// One of SPLIT_STRATEGIES
+    split: String,
+    /// Share of the token budget the field gets next to other chunkable fields of its priority
+    weight: i32,
+    /// Chunkable fields with a higher priority are budgeted before those with a lower one
+    priority: i32,
+}
+
+impl Default for ChunkableOptions {
+    fn default() -> Self {
+        ChunkableOptions {
+            overlap: 0,
+            split: "tokens".to_string(),
+            weight: 1,
+            priority: 0,
+        }
+    }
+}
+
+fn chunkable_options(field: &syn::Field, kind: &ChunkKind) -> syn::Result<ChunkableOptions> {
+    let mut options = ChunkableOptions::default();
+
+    for attr in field.attrs.iter().filter(|attr| attr.path().is_ident("chunkable")) {
+        // a bare #[chunkable] keeps the defaults
+        if let syn::Meta::Path(_) = attr.meta {
+            continue;
+        }
+
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("overlap") {
+                let overlap: syn::LitInt = meta.value()?.parse()?;
+                options.overlap = overlap.base10_parse()?;
+                return Ok(());
+            }
+
+            if meta.path.is_ident("split") {
+                let split: syn::LitStr = meta.value()?.parse()?;
+                if let ChunkKind::Elements = kind {
+                    return Err(syn::Error::new(split.span(), "collections are always split between their elements"));
+                }
+
+                if !SPLIT_STRATEGIES.contains(&split.value().as_str()) {
+                    let message = format!("split must be one of {}", SPLIT_STRATEGIES.join(", "));
+                    return Err(syn::Error::new(split.span(), message));
+                }
+
+                options.split = split.value();
+                return Ok(());
+            }
+
+            if meta.path.is_ident("weight") {
+                let weight: syn::LitInt = meta.value()?.parse()?;
+                options.weight = weight.base10_parse()?;
+                if options.weight < 1 {
+                    return Err(syn::Error::new(weight.span(), "weight must be at least 1"));
+                }
+                return Ok(());
+            }
+
+            if meta.path.is_ident("priority") {
+                let priority: syn::LitInt = meta.value()?.parse()?;
+                options.priority = priority.base10_parse()?;
+                return Ok(());
+            }
+
+            Err(meta.error("unsupported chunkable option"))
+        })?;
+    }
+
+    Ok(options)
+}
+
+/// The variant of a runtime enum that an option's value names, like RustItems for "rust_items"
+fn variant(value: &str) -> syn::Ident {
+    let name = value
+        .split('_')
+        .map(|word| {
+            let mut chars = word.chars();
+            chars.next().map(|c| c.to_ascii_uppercase().to_string() + chars.as_str()).unwrap_or_default()
+        })
+        .collect::<String>();
+
+    syn::Ident::new(&name, proc_macro2::Span::call_site())
+}
+
+/// The T of an Option<T> field, which can have generics of its own like Vec<T>
+fn get_option_type(field: &syn::Field) -> syn::Type {
+    match &field.ty {
+        syn::Type::Path(t_path) => {
+            let segments = &t_path.path.segments;
+            match &segments[0].arguments {
+                syn::PathArguments::AngleBracketed(af) => {
+                    let first_arg = af.args.first().unwrap();
+                    match first_arg {
+                        syn::GenericArgument::Type(arg) => arg.clone(),
+                        _ => unimplemented!("Path Argument not GenericArgument::Type")
+                    }
+                },
+                _ => unimplemented!("PathArgument not AngleBracketed")
+            }
+        },
+        _ => unimplemented!("Type not a path")
+    }
+}
@@ -0,0 +1,534 @@
+// The chunking that `#[derive(Promptize)]` generates code around. It works on
+// counts and ranges rather than on the fields of a struct, so the derive only
+// has to generate the parts that know about the struct and calls into here
+// for the rest, which lets any number of structs in a module derive Promptize.
+//
+// chunk_text is the part of it that is useful on its own, for text that
+// doesn't come from a struct.
+
+use crate::{PromptizeError, Tokenizer};
+use std::ops::Range;
+
+/// Where text is cut up, see #[chunkable(split = "...")]. Everything but `Tokens` prefers cutting on
+/// the end of a line, paragraph, sentence or rust item over a bare token cut.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SplitStrategy {
+    /// Anywhere between two tokens.
+    #[default]
+    Tokens,
+    Lines,
+    /// After the blank lines that end a paragraph.
+    Paragraphs,
+    /// After the whitespace that follows a '.', '!' or '?'.
+    Sentences,
+    /// Between the items of a rust file, or between the items of an impl, trait or mod when a
+    /// whole one doesn't fit. Text that doesn't parse as a rust file is split on lines.
+    RustItems,
+}
+
+/// Cuts text into chunks of at most budget tokens each, on the boundaries of the split strategy
+/// where there are any in reach. The chunks are in order and cover all of the text between them.
+///
+/// A chunk is only cut in the middle of a unit of the strategy when the unit doesn't fit in a
+/// chunk on its own, but it is never cut in the middle of a char, so this fails with
+/// BudgetTooSmall when a budget doesn't even fit the tokens of one char.
+pub fn chunk_text<'a, T: Tokenizer>(
+    text: &'a str,
+    tokenizer: &T,
+    budget: usize,
+    strategy: SplitStrategy,
+) -> Result<Vec<&'a str>, PromptizeError> {
+    let overflow = |range: Range<usize>| Ok(tokenizer.count(&text[range])?.saturating_sub(budget));
+    let chunks = chunk_string(tokenizer, text, budget, 0, strategy, overflow)?;
+
+    Ok(chunks.into_iter().map(|range| &text[range]).collect())
+}
+
+/// Gets the number of chunks it takes to fit total tokens in chunks of at most limit tokens
+pub fn get_num_chunks(total: i32, limit: i32, overlap: i32) -> i32 {
+    // a field that fits is one chunk, even when that leaves no room for an overlap
+    if total <= limit {
+        return 1;
+    }
+
+    // every chunk after the first spends overlap tokens repeating the one before it, so
+    // only limit - overlap tokens of each chunk move forward through the text
+    let remaining = std::cmp::max(total - overlap, 1) as f64;
+    let step = (limit - overlap) as f64;
+    (remaining / step).ceil() as i32
+}
+
+/// Gets the optimal chunk size in Tokens to cover total tokens in num_chunks chunks
+pub fn get_chunk_size_tokens(total: i32, num_chunks: i32, overlap: i32) -> i32 {
+    let remaining = std::cmp::max(total - overlap, 1) as f64;
+
+    // rounding up spreads the remainder over the chunks instead of leaving a runt chunk
+    // at the end, ceil(remaining / ceil(remaining / step)) can never be larger than step
+    (remaining / num_chunks as f64).ceil() as i32 + overlap
+}
+
+/// Splits the token budget between chunkable fields. Fields with a higher priority are
+/// budgeted first and whatever they don't need is left to the next priority down. Fields of
+/// the same priority share the budget by weight, a field that needs less than its share
+/// takes only what it needs and the rest is shared out again.
+pub fn allocate_budget(budget: i32, totals: &[i32], weights: &[i32], priorities: &[i32]) -> Vec<i32> {
+    let mut allocations = vec![0; totals.len()];
+    let mut budget = budget;
+
+    let mut levels = priorities.to_vec();
+    levels.sort_unstable_by(|a, b| b.cmp(a));
+    levels.dedup();
+
+    for level in levels {
+        let mut group = (0..totals.len())
+            .filter(|&i| priorities[i] == level)
+            .collect::<Vec<usize>>();
+
+        while !group.is_empty() {
+            let total_weight: i32 = group.iter().map(|&i| weights[i]).sum();
+            let share = |i: usize| budget * weights[i] / total_weight;
+
+            let (fits, rest): (Vec<usize>, Vec<usize>) = group
+                .iter()
+                .partition(|&&i| totals[i] <= share(i));
+
+            if fits.is_empty() {
+                for &i in &rest {
+                    allocations[i] = share(i);
+                }
+                budget -= rest.iter().map(|&i| allocations[i]).sum::<i32>();
+                break;
+            }
+
+            for &i in &fits {
+                allocations[i] = totals[i];
+                budget -= totals[i];
+            }
+            group = rest;
+        }
+    }
+
+    allocations
+}
+
+/// Lines up the nth chunk of every field. A field that is in one piece goes out with every
+/// chunk, and fields that run out of chunks are left empty.
+pub fn zip_chunks(
+    field_chunks: &[Vec<Range<usize>>]
+) -> Vec<Vec<Range<usize>>> {
+    let len = field_chunks.iter().map(|chunks| chunks.len()).max().unwrap_or(0);
+
+    (0..len)
+        .map(|n| {
+            field_chunks
+                .iter()
+                .map(|chunks| match chunks.len() {
+                    1 => chunks[0].clone(),
+                    _ => chunks.get(n).cloned().unwrap_or(0..0)
+                })
+                .collect()
+        })
+        .collect()
+}
+
+/// Every combination of one chunk from each field
+pub fn cartesian_chunks(
+    field_chunks: &[Vec<Range<usize>>]
+) -> Vec<Vec<Range<usize>>> {
+    field_chunks.iter().fold(vec![vec![]], |combinations, chunks| {
+        combinations
+            .iter()
+            .flat_map(|combination| {
+                chunks.iter().map(move |chunk| {
+                    let mut combination = combination.clone();
+                    combination.push(chunk.clone());
+                    combination
+                })
+            })
+            .collect()
+    })
+}
+
+/// Bytes of text first encoded for every token of a chunk, about what a token of English takes.
+/// Text that takes more bytes a token gets a bigger window.
+const WINDOW_BYTES_PER_TOKEN: usize = 4;
+
+/// Chunks up a string on token boundaries into byte ranges of it. Each chunk starts out as
+/// chunk_size tokens of the string, is cut back to the last natural boundary of the `split`
+/// strategy if there is one, and is shrunk until `overflow`, the number of tokens the chunk
+/// goes over budget by once it is placed in its request, comes back as 0. Every chunk after
+/// the first starts with the last `overlap` tokens of the chunk before it, or with the whole
+/// units that fit in them.
+pub fn chunk_string<T, F>(
+    tokenizer: &T,
+    prompt: &str,
+    chunk_size: usize,
+    overlap: usize,
+    split: SplitStrategy,
+    overflow: F
+) -> Result<Vec<Range<usize>>, PromptizeError>
+where
+    T: Tokenizer,
+    F: Fn(Range<usize>) -> Result<usize, PromptizeError>
+{
+    let boundaries = split_boundaries(prompt, split);
+
+    let mut chunks = vec![];
+    let mut start: usize = 0;
+    // bytes that have made it into a chunk so far, a chunk has to go past this
+    let mut covered = 0;
+
+    while covered < prompt.len() {
+        // only a window of the rest of the text is encoded, or chunking would take time quadratic
+        // in its length. The window grows until it has a token more than a chunk, so that the last
+        // token, which the end of the window may have cut short, is never part of one.
+        let mut window = chunk_size.saturating_add(1).saturating_mul(WINDOW_BYTES_PER_TOKEN);
+        let (token_ends, rest) = loop {
+            let mut end = start.saturating_add(window).min(prompt.len());
+            while !prompt.is_char_boundary(end) {
+                end += 1;
+            }
+
+            let token_ends = tokenizer.token_ends(&prompt[start..end])?;
+            if end == prompt.len() || token_ends.len() > chunk_size {
+                break (token_ends, end == prompt.len());
+            }
+            window = window.saturating_mul(2);
+        };
+        let mut size = std::cmp::min(chunk_size, token_ends.len());
+
+        let end = loop {
+            // BPE tokens are bytes, so a boundary can land inside of a multibyte char and we
+            // back off a token. A tokenizer that drops whitespace can end its last token before
+            // the end of the text, the rest of the text goes with it.
+            let mut end = match size.checked_sub(1).map(|last| token_ends[last]) {
+                _ if rest && size == token_ends.len() => prompt.len(),
+                Some(Some(end)) => start + end,
+                Some(None) => {
+                    size -= 1;
+                    continue;
+                },
+                None => start
+            };
+
+            if end <= covered {
+                // not even the overlap and one more char fit, report what that would take
+                let min_end = covered + prompt[covered..].chars().next().map_or(0, char::len_utf8);
+                let min_chunk = &prompt[start..min_end];
+                let required = tokenizer.count(min_chunk)? + overflow(start..min_end)?;
+
+                return Err(PromptizeError::BudgetTooSmall {
+                    available: chunk_size as i32,
+                    required: required as i32
+                });
+            }
+
+            // a unit is only cut in half when no boundary at all fits in the chunk
+            if end < prompt.len() {
+                let boundary = boundaries.iter().find_map(|level| {
+                    let i = level.partition_point(|&b| b <= end);
+                    level[..i].last().filter(|&&b| b > covered)
+                });
+
+                if let Some(&boundary) = boundary {
+                    end = boundary;
+                }
+            }
+
+            // Re-encoding a chunk on its own can merge differently than it did in the full
+            // string, so the count is checked again rather than trusted.
+            match overflow(start..end)? {
+                0 => break end,
+                over => {
+                    let chunk_tokens = tokenizer.count(&prompt[start..end])?;
+                    size = std::cmp::min(size - 1, chunk_tokens.saturating_sub(over));
+                }
+            }
+        };
+
+        chunks.push(start..end);
+        covered = end;
+
+        if overlap == 0 {
+            start = end;
+            continue;
+        }
+
+        // step back into the chunk so the next one repeats its tail, which starts where the token
+        // before it ends, nudging forward again if that lands inside of a multibyte char
+        let chunk_token_ends = tokenizer.token_ends(&prompt[start..end])?;
+        let tail = chunk_token_ends.len().saturating_sub(overlap);
+        let next = (tail..chunk_token_ends.len())
+            .find_map(|i| match i {
+                0 => Some(0),
+                i => chunk_token_ends[i - 1]
+            })
+            .map_or(end, |offset| start + offset);
+
+        // whole units read better than a unit cut in half when any fit in the overlap
+        let boundary = boundaries
+            .iter()
+            .find_map(|level| level.iter().find(|&&b| b >= next && b < end));
+
+        start = std::cmp::max(boundary.copied().unwrap_or(next), start);
+    }
+
+    Ok(chunks)
+}
+
+/// Chunks up a collection into ranges of whole elements, given the tokens each element takes.
+/// A chunk takes elements until the next one would put it over chunk_size tokens, and drops
+/// elements off the end again while `overflow`, the number of tokens the chunk goes over
+/// budget by once it is placed in its request, is more than 0. Every chunk after the first
+/// starts with the elements at the end of the chunk before it that fit in `overlap` tokens.
+pub fn chunk_elements<F>(
+    element_tokens: &[i32],
+    chunk_size: usize,
+    overlap: usize,
+    overflow: F
+) -> Result<Vec<Range<usize>>, PromptizeError>
+where
+    F: Fn(Range<usize>) -> Result<usize, PromptizeError>
+{
+    let tokens = |range: Range<usize>| element_tokens[range].iter().sum::<i32>() as usize;
+
+    let mut chunks = vec![];
+    let mut start = 0;
+    // elements that have made it into a chunk so far, a chunk has to go past this
+    let mut covered = 0;
+
+    while covered < element_tokens.len() {
+        let mut end = covered + 1;
+        while end < element_tokens.len() && tokens(start..end + 1) <= chunk_size {
+            end += 1;
+        }
+
+        loop {
+            let mut over = overflow(start..end)?;
+            if over == 0 {
+                break;
+            }
+
+            if end == covered + 1 {
+                // not even the overlap and one more element fit, report what that would take
+                return Err(PromptizeError::BudgetTooSmall {
+                    available: chunk_size as i32,
+                    required: (tokens(start..end) + over) as i32
+                });
+            }
+
+            // drop as many elements as it takes to make up for the tokens over
+            while end > covered + 1 && over > 0 {
+                end -= 1;
+                over = over.saturating_sub(element_tokens[end] as usize);
+            }
+        }
+
+        chunks.push(start..end);
+        covered = end;
+
+        // step back over the elements at the end of the chunk that fit in the overlap
+        start = end;
+        while start > chunks.last().unwrap().start + 1 && tokens(start - 1..end) <= overlap {
+            start -= 1;
+        }
+    }
+
+    Ok(chunks)
+}
+
+/// Byte offsets the `split` strategy can cut the text at without breaking up one of its
+/// units. Each offset is the end of a unit. Offsets are grouped into levels, coarsest first,
+/// so that a finer boundary is only used when no coarser one fits.
+fn split_boundaries(text: &str, split: SplitStrategy) -> Vec<Vec<usize>> {
+    let lines = text
+        .match_indices('\n')
+        .map(|(i, _)| i + 1)
+        .collect::<Vec<usize>>();
+
+    match split {
+        SplitStrategy::Lines => vec![lines],
+        SplitStrategy::Paragraphs => {
+            // a paragraph ends where the blank lines after it do
+            let paragraphs = lines
+                .windows(2)
+                .filter(|pair| text[pair[0]..pair[1]].trim().is_empty())
+                .filter(|pair| text[pair[1]..].lines().next().is_some_and(|line| !line.trim().is_empty()))
+                .map(|pair| pair[1])
+                .collect();
+
+            vec![paragraphs]
+        },
+        SplitStrategy::Sentences => {
+            let chars = text.char_indices().collect::<Vec<_>>();
+            let sentences = chars
+                .windows(2)
+                .filter(|pair| matches!(pair[0].1, '.' | '!' | '?') && pair[1].1.is_whitespace())
+                .map(|pair| {
+                    // the whitespace after a sentence stays with it
+                    text[pair[1].0..]
+                        .find(|c: char| !c.is_whitespace())
+                        .map(|i| pair[1].0 + i)
+                        .unwrap_or(text.len())
+                })
+                .collect();
+
+            vec![sentences]
+        },
+        SplitStrategy::RustItems => {
+            // span-locations keeps a copy of every file parsed on a thread for as long as the thread
+            // lives, and its offsets overflow after 4 GiB. Parsing on a thread of its own frees the
+            // copy as soon as the lines are out, and leaves the caller's spans alone.
+            std::thread::scope(|scope| {
+                scope
+                    .spawn(|| rust_item_boundaries(text, &lines))
+                    .join()
+                    .unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+            })
+        },
+        SplitStrategy::Tokens => vec![]
+    }
+}
+
+/// The boundaries of SplitStrategy::RustItems, given the ends of the lines of the text.
+fn rust_item_boundaries(text: &str, lines: &[usize]) -> Vec<Vec<usize>> {
+    use syn::spanned::Spanned;
+
+    let file = match syn::parse_file(text) {
+        Ok(file) => file,
+        // not valid rust on its own, lines are the next best thing
+        Err(_) => return vec![lines.to_vec()]
+    };
+
+    // items end at the end of their last line, spans count lines from 1
+    let line_end = |line: usize| lines.get(line - 1).copied().unwrap_or(text.len());
+
+    let items = file.items
+        .iter()
+        .map(|item| line_end(item.span().end().line))
+        .collect();
+
+    let nested_items = file.items
+        .iter()
+        .flat_map(|item| match item {
+            syn::Item::Impl(item) => item.items.iter().map(|i| i.span().end().line).collect(),
+            syn::Item::Trait(item) => item.items.iter().map(|i| i.span().end().line).collect(),
+            syn::Item::Mod(item) => item.content
+                .iter()
+                .flat_map(|(_, items)| items.iter().map(|i| i.span().end().line))
+                .collect(),
+            _ => vec![]
+        })
+        .map(line_end)
+        .collect();
+
+    vec![items, nested_items]
+}
+
+pub fn get_prompt_tokens<T: Tokenizer>(tokenizer: &T, prompt: &str) -> Result<i32, PromptizeError> {
+    Ok(tokenizer.count(prompt)? as i32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{HeuristicTokenizer, TiktokenTokenizer};
+
+    #[test]
+    fn chunks_cover_the_text_within_budget() {
+        let tokenizer = TiktokenTokenizer::for_model("gpt-4").unwrap();
+        let text = "The quick brown fox jumps over the lazy dog. ".repeat(200);
+
+        let chunks = chunk_text(&text, &tokenizer, 100, SplitStrategy::Tokens).unwrap();
+
+        assert!(chunks.len() > 1);
+        assert_eq!(chunks.concat(), text);
+        for chunk in &chunks {
+            assert!(tokenizer.count(chunk).unwrap() <= 100);
+        }
+    }
+
+    #[test]
+    fn chunks_are_full_when_tokens_take_more_bytes_than_the_window_expects() {
+        let tokenizer = HeuristicTokenizer::new(7);
+        let text = "abcdefgabcdefgabcdefg ".repeat(500);
+
+        let chunks = chunk_text(&text, &tokenizer, 100, SplitStrategy::Tokens).unwrap();
+
+        assert_eq!(chunks.concat(), text);
+        for chunk in &chunks[..chunks.len() - 1] {
+            assert_eq!(tokenizer.count(chunk).unwrap(), 100);
+        }
+    }
+
+    #[test]
+    fn chunks_end_on_the_boundaries_of_the_strategy() {
+        let tokenizer = HeuristicTokenizer::default();
+        let text = (0..300).map(|i| format!("line number {}\n", i)).collect::<String>();
+
+        let chunks = chunk_text(&text, &tokenizer, 50, SplitStrategy::Lines).unwrap();
+
+        assert!(chunks.len() > 1);
+        assert_eq!(chunks.concat(), text);
+        for chunk in &chunks {
+            assert!(chunk.ends_with('\n'));
+            assert!(tokenizer.count(chunk).unwrap() <= 50);
+        }
+
+        let text = "One sentence here. Another one there! And a question? ".repeat(50);
+        let chunks = chunk_text(&text, &tokenizer, 30, SplitStrategy::Sentences).unwrap();
+        for chunk in &chunks {
+            assert!(chunk.trim_end().ends_with(['.', '!', '?']));
+        }
+    }
+
+    #[test]
+    fn rust_items_are_kept_whole() {
+        let tokenizer = TiktokenTokenizer::for_model("gpt-4").unwrap();
+        let text = (0..40)
+            .map(|i| format!("fn function_{}(a: i32) -> i32 {{\n    a * {}\n}}\n", i, i))
+            .collect::<String>();
+
+        let chunks = chunk_text(&text, &tokenizer, 80, SplitStrategy::RustItems).unwrap();
+
+        assert!(chunks.len() > 1);
+        for chunk in &chunks {
+            assert!(syn::parse_file(chunk).is_ok());
+        }
+    }
+
+    #[test]
+    fn rust_items_can_be_split_over_and_over() {
+        let tokenizer = HeuristicTokenizer::default();
+        let text = (0..50)
+            .map(|i| format!("fn function_{}(a: i32) -> i32 {{\n    a * {}\n}}\n", i, i))
+            .collect::<String>();
+
+        // spans of the caller's own parse are left alone by the parses of the split
+        let file = syn::parse_file(&text).unwrap();
+        let span = syn::spanned::Spanned::span(&file.items[1]);
+
+        let boundaries = split_boundaries(&text, SplitStrategy::RustItems);
+        for _ in 0..500 {
+            assert_eq!(split_boundaries(&text, SplitStrategy::RustItems), boundaries);
+        }
+
+        let chunks = chunk_text(&text, &tokenizer, 100, SplitStrategy::RustItems).unwrap();
+        assert_eq!(chunks.concat(), text);
+        assert_eq!(span.start().line, 4);
+    }
+
+    #[test]
+    fn text_that_fits_is_one_chunk() {
+        let tokenizer = HeuristicTokenizer::default();
+        assert_eq!(chunk_text("short", &tokenizer, 100, SplitStrategy::Tokens).unwrap(), vec!["short"]);
+        assert!(chunk_text("", &tokenizer, 100, SplitStrategy::Tokens).unwrap().is_empty());
+    }
+
+    #[test]
+    fn a_budget_without_room_for_a_char_fails() {
+        let tokenizer = TiktokenTokenizer::for_model("gpt-4").unwrap();
+        let error = chunk_text("some text", &tokenizer, 0, SplitStrategy::Tokens).unwrap_err();
+        assert!(matches!(error, PromptizeError::BudgetTooSmall { .. }));
+    }
+}
@@ -0,0 +1,332 @@
+// Sends the requests of a plan to a chat completions endpoint, OpenAI's or
+// any server that speaks its API, so that running a chunked prompt doesn't
+// take a client of its own.
+
+use crate::{ChatCompletionRequestMessage, PromptPlan};
+use futures::stream::{self, StreamExt, TryStreamExt};
+use std::time::Duration;
+
+/// Everything that can go wrong sending a request with a `Client`.
+#[derive(Debug)]
+pub enum ClientError {
+    /// The request could not be sent or its response could not be read.
+    Http(reqwest::Error),
+    /// The endpoint answered with an error status, after as many retries as it was worth.
+    Status { status: u16, body: String },
+    /// The response has no message in it.
+    InvalidResponse(String),
+}
+
+impl std::fmt::Display for ClientError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ClientError::Http(error) => write!(f, "request failed: {}", error),
+            ClientError::Status { status, body } => write!(f, "endpoint answered {}: {}", status, body),
+            ClientError::InvalidResponse(body) => write!(f, "no message in response: {}", body),
+        }
+    }
+}
+
+impl std::error::Error for ClientError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ClientError::Http(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<reqwest::Error> for ClientError {
+    fn from(error: reqwest::Error) -> Self {
+        ClientError::Http(error)
+    }
+}
+
+/// Sends chat requests to an OpenAI-compatible endpoint. Requests that are rate limited or hit a
+/// server error are retried after a backoff that doubles every time, or after as long as the
+/// server's Retry-After asks for, up to the backoff of the last retry.
+#[derive(Clone)]
+pub struct Client {
+    http: reqwest::Client,
+    base_url: String,
+    model: String,
+    api_key: Option<String>,
+    concurrency: usize,
+    max_retries: u32,
+    backoff: Duration,
+    timeout: Duration,
+}
+
+impl Client {
+    /// A client for `model` at `base_url`, like "https://api.openai.com/v1". Requests go to
+    /// {base_url}/chat/completions, 4 at a time, are given up on after 5 minutes, and are retried
+    /// 3 times starting a second apart.
+    pub fn new(base_url: impl Into<String>, model: impl Into<String>) -> Self {
+        Client {
+            http: reqwest::Client::new(),
+            base_url: base_url.into().trim_end_matches('/').to_string(),
+            model: model.into(),
+            api_key: None,
+            concurrency: 4,
+            max_retries: 3,
+            backoff: Duration::from_secs(1),
+            timeout: Duration::from_secs(300),
+        }
+    }
+
+    /// Sets the key sent as a bearer token with every request.
+    pub fn api_key(mut self, api_key: impl Into<String>) -> Self {
+        self.api_key = Some(api_key.into());
+        self
+    }
+
+    /// Sets the number of requests of a plan that are in flight at once, at least 1.
+    pub fn concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency.max(1);
+        self
+    }
+
+    /// Sets the number of times a request is retried before its error is given up on.
+    pub fn max_retries(mut self, max_retries: u32) -> Self {
+        self.max_retries = max_retries;
+        self
+    }
+
+    /// Sets how long to wait before the first retry of a request.
+    pub fn backoff(mut self, backoff: Duration) -> Self {
+        self.backoff = backoff;
+        self
+    }
+
+    /// Sets how long a request has to get its whole answer before it fails with a timeout.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Sends every request of a plan and returns the answers in the order of its chunks. The first
+    /// request that fails for good fails the plan.
+    pub async fn execute(&self, plan: &PromptPlan) -> Result<Vec<String>, ClientError> {
+        stream::iter(plan.iter())
+            .map(|chunk| self.complete(&chunk.messages))
+            .buffered(self.concurrency)
+            .try_collect()
+            .await
+    }
+
+    /// Sends one request and returns the content of the message the model answers with.
+    pub async fn complete(&self, messages: &[ChatCompletionRequestMessage]) -> Result<String, ClientError> {
+        let url = format!("{}/chat/completions", self.base_url);
+        let messages = messages
+            .iter()
+            .map(|message| {
+                let mut json = serde_json::json!({ "role": message.role, "content": message.content });
+                if let Some(name) = &message.name {
+                    json["name"] = name.as_str().into();
+                }
+                json
+            })
+            .collect::<Vec<_>>();
+        let body = serde_json::json!({ "model": self.model, "messages": messages });
+
+        let mut retries = 0;
+        loop {
+            let mut request = self.http.post(&url).timeout(self.timeout).json(&body);
+            if let Some(api_key) = &self.api_key {
+                request = request.bearer_auth(api_key);
+            }
+
+            let response = request.send().await?;
+            let status = response.status();
+
+            if status.is_success() {
+                let body: serde_json::Value = response.json().await?;
+                return body["choices"][0]["message"]["content"]
+                    .as_str()
+                    .map(str::to_string)
+                    .ok_or_else(|| ClientError::InvalidResponse(body.to_string()));
+            }
+
+            let retryable = status == reqwest::StatusCode::TOO_MANY_REQUESTS || status.is_server_error();
+            if !retryable || retries >= self.max_retries {
+                return Err(ClientError::Status {
+                    status: status.as_u16(),
+                    body: response.text().await.unwrap_or_default(),
+                });
+            }
+
+            let retry_after = response
+                .headers()
+                .get(reqwest::header::RETRY_AFTER)
+                .and_then(|value| value.to_str().ok())
+                .and_then(|value| value.parse().ok())
+                .map(Duration::from_secs);
+            let backoff = self.backoff.saturating_mul(2u32.saturating_pow(retries));
+
+            // a server can ask for any wait at all, it gets no more than the last retry would
+            let max_backoff = self.backoff.saturating_mul(2u32.saturating_pow(self.max_retries));
+            tokio::time::sleep(retry_after.unwrap_or(backoff).min(max_backoff)).await;
+            retries += 1;
+        }
+    }
+}
+
+// run with `cargo test --features client`
+#[cfg(all(test, feature = "client"))]
+mod tests {
+    use super::*;
+    use crate::{PromptChunk, Role};
+    use std::time::Instant;
+    use wiremock::matchers::{header, method, path};
+    use wiremock::{Mock, MockServer, Request, Respond, ResponseTemplate};
+
+    fn completion(content: &str) -> ResponseTemplate {
+        ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "choices": [{ "index": 0, "message": { "role": "assistant", "content": content } }]
+        }))
+    }
+
+    fn plan(requests: usize) -> PromptPlan {
+        let chunks = (0..requests)
+            .map(|index| PromptChunk {
+                messages: vec![Role::System.message("Summarize."), Role::User.message(format!("chunk {}", index))],
+                tokens: 0,
+                index,
+                total: requests,
+                sources: vec![],
+            })
+            .collect();
+
+        PromptPlan { chunks, truncations: vec![] }
+    }
+
+    /// Answers with the content of the last message, later chunks quicker than earlier ones.
+    struct Echo;
+
+    impl Respond for Echo {
+        fn respond(&self, request: &Request) -> ResponseTemplate {
+            let body: serde_json::Value = serde_json::from_slice(&request.body).unwrap();
+            let content = body["messages"][1]["content"].as_str().unwrap();
+            let index: u64 = content.trim_start_matches("chunk ").parse().unwrap();
+
+            completion(&format!("answer to {}", content)).set_delay(Duration::from_millis(200 - index * 20))
+        }
+    }
+
+    #[tokio::test]
+    async fn answers_come_back_in_chunk_order() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/v1/chat/completions"))
+            .and(header("authorization", "Bearer secret"))
+            .respond_with(Echo)
+            .mount(&server)
+            .await;
+
+        let client = Client::new(format!("{}/v1/", server.uri()), "gpt-4").api_key("secret").concurrency(8);
+        let answers = client.execute(&plan(8)).await.unwrap();
+
+        let expected = (0..8).map(|i| format!("answer to chunk {}", i)).collect::<Vec<_>>();
+        assert_eq!(answers, expected);
+
+        let request: serde_json::Value = serde_json::from_slice(&server.received_requests().await.unwrap()[0].body).unwrap();
+        assert_eq!(request["model"], "gpt-4");
+        assert_eq!(request["messages"][0], serde_json::json!({ "role": "system", "content": "Summarize." }));
+    }
+
+    #[tokio::test]
+    async fn no_more_requests_than_the_concurrency_are_in_flight() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(completion("ok").set_delay(Duration::from_millis(200)))
+            .mount(&server)
+            .await;
+
+        let start = Instant::now();
+        let answers = Client::new(server.uri(), "gpt-4").concurrency(2).execute(&plan(6)).await.unwrap();
+
+        // 6 requests 2 at a time take at least 3 rounds
+        assert_eq!(answers.len(), 6);
+        assert!(start.elapsed() >= Duration::from_millis(600));
+    }
+
+    #[tokio::test]
+    async fn rate_limits_and_server_errors_are_retried() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(429))
+            .up_to_n_times(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(503))
+            .up_to_n_times(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST")).respond_with(completion("ok")).mount(&server).await;
+
+        let client = Client::new(server.uri(), "gpt-4").backoff(Duration::from_millis(10));
+        let answer = client.complete(&plan(1)[0].messages).await.unwrap();
+
+        assert_eq!(answer, "ok");
+        assert_eq!(server.received_requests().await.unwrap().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn retries_run_out() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(500).set_body_string("overloaded"))
+            .mount(&server)
+            .await;
+
+        let client = Client::new(server.uri(), "gpt-4").max_retries(2).backoff(Duration::from_millis(10));
+        let error = client.execute(&plan(1)).await.unwrap_err();
+
+        assert!(matches!(error, ClientError::Status { status: 500, ref body } if body == "overloaded"));
+        assert_eq!(server.received_requests().await.unwrap().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn retry_after_waits_no_longer_than_the_last_backoff() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(429).insert_header("retry-after", "3600"))
+            .up_to_n_times(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST")).respond_with(completion("ok")).mount(&server).await;
+
+        let client = Client::new(server.uri(), "gpt-4").max_retries(2).backoff(Duration::from_millis(10));
+        let start = Instant::now();
+        let answer = client.complete(&plan(1)[0].messages).await.unwrap();
+
+        assert_eq!(answer, "ok");
+        assert!(start.elapsed() < Duration::from_secs(5));
+    }
+
+    #[tokio::test]
+    async fn requests_time_out() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(completion("ok").set_delay(Duration::from_secs(5)))
+            .mount(&server)
+            .await;
+
+        let client = Client::new(server.uri(), "gpt-4").timeout(Duration::from_millis(100));
+        let error = client.complete(&plan(1)[0].messages).await.unwrap_err();
+
+        assert!(matches!(error, ClientError::Http(ref error) if error.is_timeout()));
+    }
+
+    #[tokio::test]
+    async fn client_errors_are_not_retried() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST")).respond_with(ResponseTemplate::new(400)).mount(&server).await;
+
+        let error = Client::new(server.uri(), "gpt-4").complete(&plan(1)[0].messages).await.unwrap_err();
+
+        assert!(matches!(error, ClientError::Status { status: 400, .. }));
+        assert_eq!(server.received_requests().await.unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,85 @@
+// The chat messages prompts are sent as. They are tiktoken's, so they can be
+// counted with tiktoken's own functions too.
+
+use crate::{PromptizeError, Tokenizer};
+
+pub use tiktoken_rs::ChatCompletionRequestMessage;
+
+/// Who a chat message is from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Role {
+    System,
+    User,
+    Assistant,
+}
+
+impl Role {
+    /// The name of the role in the chat API.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Role::System => "system",
+            Role::User => "user",
+            Role::Assistant => "assistant",
+        }
+    }
+
+    /// A message with content from the role.
+    pub fn message(self, content: impl Into<String>) -> ChatCompletionRequestMessage {
+        ChatCompletionRequestMessage {
+            role: self.as_str().to_string(),
+            content: content.into(),
+            name: None,
+        }
+    }
+}
+
+/// The most recent turns of history that take at most budget tokens in a chat. With a summarize
+/// hook, the turns that are dropped are replaced by its summary of them as long as that fits too,
+/// dropping more turns to make room for it if it has to.
+pub fn fit_history<T: Tokenizer + ?Sized>(
+    tokenizer: &T,
+    history: &[ChatCompletionRequestMessage],
+    budget: i32,
+    summarize: Option<fn(&[ChatCompletionRequestMessage]) -> ChatCompletionRequestMessage>,
+) -> Result<Vec<ChatCompletionRequestMessage>, PromptizeError> {
+    // a message takes the same tokens wherever it goes in a chat, the priming of the reply is only
+    // counted once for the whole chat
+    let reply = tokenizer.count_messages(&[])? as i32;
+    let cost = |message: &ChatCompletionRequestMessage| -> Result<i32, PromptizeError> {
+        Ok(tokenizer.count_messages(std::slice::from_ref(message))? as i32 - reply)
+    };
+    let costs = history.iter().map(cost).collect::<Result<Vec<i32>, PromptizeError>>()?;
+
+    let mut start = history.len();
+    let mut tokens = 0;
+    while start > 0 && tokens + costs[start - 1] <= budget {
+        start -= 1;
+        tokens += costs[start];
+    }
+
+    if start == 0 {
+        return Ok(history.to_vec());
+    }
+
+    // without a summary that fits, the turns that fit on their own are all that is left
+    let kept = history[start..].to_vec();
+
+    if let Some(summarize) = summarize {
+        loop {
+            let summary = summarize(&history[..start]);
+            if tokens + cost(&summary)? <= budget {
+                let mut fitted = vec![summary];
+                fitted.extend_from_slice(&history[start..]);
+                return Ok(fitted);
+            }
+
+            if start == history.len() {
+                break;
+            }
+            tokens -= costs[start];
+            start += 1;
+        }
+    }
+
+    Ok(kept)
+}
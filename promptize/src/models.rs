@@ -0,0 +1,147 @@
+// What build_prompt_for needs to know about a model to work out its own
+// limits: how big the context window is, how text is tokenized and how many
+// tokens the chat format adds around every message. OpenAI's chat models are
+// built in and anything else can be registered next to them.
+
+use crate::tokenizer::count_chat_tokens;
+use crate::{PromptizeError, TiktokenTokenizer, Tokenizer};
+use std::collections::HashMap;
+use std::sync::{Arc, OnceLock, RwLock};
+
+/// A model that prompts are built for.
+#[derive(Clone)]
+pub struct ModelInfo {
+    /// Tokens the model can take in and put out in one request, prompt and completion together.
+    pub context_window: i32,
+    /// Tokens every message costs on top of its role and content, for the separators around it.
+    pub tokens_per_message: i32,
+    /// Tokens a message with a name costs on top of the name itself.
+    pub tokens_per_name: i32,
+    /// Tokens of the priming every reply starts with.
+    pub tokens_per_reply: i32,
+    /// Dollars a million prompt tokens cost, if the price is known.
+    pub input_price: Option<f64>,
+    tokenizer: Arc<dyn Tokenizer + Send + Sync>,
+}
+
+impl ModelInfo {
+    /// A model with a context window of `context_window` tokens and OpenAI's chat format overhead.
+    pub fn new(context_window: i32, tokenizer: impl Tokenizer + Send + Sync + 'static) -> Self {
+        ModelInfo {
+            context_window,
+            tokens_per_message: 3,
+            tokens_per_name: 1,
+            tokens_per_reply: 3,
+            input_price: None,
+            tokenizer: Arc::new(tokenizer),
+        }
+    }
+
+    /// Sets the tokens every message and every name costs on top of their text.
+    pub fn message_overhead(mut self, tokens_per_message: i32, tokens_per_name: i32) -> Self {
+        self.tokens_per_message = tokens_per_message;
+        self.tokens_per_name = tokens_per_name;
+        self
+    }
+
+    /// Sets the tokens of the priming every reply starts with.
+    pub fn reply_overhead(mut self, tokens_per_reply: i32) -> Self {
+        self.tokens_per_reply = tokens_per_reply;
+        self
+    }
+
+    /// Sets the dollars a million prompt tokens cost, for estimates.
+    pub fn input_price(mut self, dollars_per_million_tokens: f64) -> Self {
+        self.input_price = Some(dollars_per_million_tokens);
+        self
+    }
+
+    /// The tokenizer the model uses.
+    pub fn tokenizer(&self) -> &(dyn Tokenizer + Send + Sync) {
+        &*self.tokenizer
+    }
+}
+
+/// A model counts and cuts tokens with its tokenizer and counts messages with its own overhead.
+impl Tokenizer for ModelInfo {
+    fn encode(&self, text: &str) -> Result<Vec<usize>, PromptizeError> {
+        self.tokenizer.encode(text)
+    }
+
+    fn decode(&self, tokens: &[usize]) -> Option<String> {
+        self.tokenizer.decode(tokens)
+    }
+
+    fn token_ends(&self, text: &str) -> Result<Vec<Option<usize>>, PromptizeError> {
+        self.tokenizer.token_ends(text)
+    }
+
+    fn count(&self, text: &str) -> Result<usize, PromptizeError> {
+        self.tokenizer.count(text)
+    }
+
+    fn count_messages(&self, messages: &[crate::ChatCompletionRequestMessage]) -> Result<usize, PromptizeError> {
+        count_chat_tokens(
+            self,
+            messages,
+            self.tokens_per_message,
+            self.tokens_per_name,
+            self.tokens_per_reply,
+        )
+    }
+}
+
+/// OpenAI's chat models as (name prefix, context window, tokens per message, tokens per name,
+/// dollars per million prompt tokens). The longest prefix that matches a model name wins, so dated
+/// snapshots like "gpt-4-0613" get the context window of the model they are a snapshot of. Prices
+/// change, registering a model with a different input_price overrides the one here.
+const OPENAI_MODELS: &[(&str, i32, i32, i32, f64)] = &[
+    ("gpt-4-32k", 32768, 3, 1, 60.0),
+    ("gpt-4-turbo", 128000, 3, 1, 10.0),
+    ("gpt-4-1106", 128000, 3, 1, 10.0),
+    ("gpt-4-0125", 128000, 3, 1, 10.0),
+    ("gpt-4", 8192, 3, 1, 30.0),
+    ("gpt-3.5-turbo-16k", 16384, 3, 1, 3.0),
+    ("gpt-3.5-turbo-1106", 16385, 3, 1, 1.0),
+    ("gpt-3.5-turbo-0125", 16385, 3, 1, 0.5),
+    // every message follows <im_start>{role/name}\n{content}<im_end>\n and a name replaces the role
+    ("gpt-3.5-turbo-0301", 4096, 4, -1, 1.5),
+    ("gpt-3.5-turbo", 4096, 3, 1, 0.5),
+];
+
+fn registered_models() -> &'static RwLock<HashMap<String, ModelInfo>> {
+    static MODELS: OnceLock<RwLock<HashMap<String, ModelInfo>>> = OnceLock::new();
+    MODELS.get_or_init(Default::default)
+}
+
+/// Registers a model under `name`, for models that aren't built in or to change one that is.
+pub fn register_model(name: impl Into<String>, info: ModelInfo) {
+    registered_models()
+        .write()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .insert(name.into(), info);
+}
+
+/// Looks a model up, first among the registered models and then among OpenAI's chat models.
+pub fn model_info(model: &str) -> Result<ModelInfo, PromptizeError> {
+    let registered = registered_models()
+        .read()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .get(model)
+        .cloned();
+
+    if let Some(info) = registered {
+        return Ok(info);
+    }
+
+    let (_, context_window, tokens_per_message, tokens_per_name, input_price) = OPENAI_MODELS
+        .iter()
+        .filter(|(prefix, ..)| model.starts_with(prefix))
+        .max_by_key(|(prefix, ..)| prefix.len())
+        .ok_or_else(|| PromptizeError::UnknownModel(model.to_string()))?;
+
+    let tokenizer = TiktokenTokenizer::for_model(model)?;
+    Ok(ModelInfo::new(*context_window, tokenizer)
+        .message_overhead(*tokens_per_message, *tokens_per_name)
+        .input_price(*input_price))
+}
@@ -0,0 +1,190 @@
+// What build_prompt hands back: the requests to send, in order, and for each
+// of them the part of every chunkable field it covers, so that answers to a
+// chunk can be traced back to where they came from. Or, before any of that,
+// an estimate of what sending them would take. When fields had to be cut
+// short to make room for the chunks, what was cut is in there too.
+
+use std::ops::Range;
+
+/// The requests a prompt was cut into, in the order the chunks come in their fields.
+#[derive(Debug, Clone)]
+pub struct PromptPlan {
+    pub chunks: Vec<PromptChunk>,
+    /// The #[truncatable] fields that were cut short to fit the prompt, in the order they were cut.
+    pub truncations: Vec<Truncation>,
+}
+
+impl PromptPlan {
+    /// The number of requests.
+    pub fn len(&self) -> usize {
+        self.chunks.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.chunks.is_empty()
+    }
+
+    pub fn iter(&self) -> std::slice::Iter<'_, PromptChunk> {
+        self.chunks.iter()
+    }
+
+    /// Just the messages of every request.
+    pub fn into_messages(self) -> Vec<Vec<crate::ChatCompletionRequestMessage>> {
+        self.chunks.into_iter().map(|chunk| chunk.messages).collect()
+    }
+}
+
+impl std::ops::Index<usize> for PromptPlan {
+    type Output = PromptChunk;
+
+    fn index(&self, index: usize) -> &PromptChunk {
+        &self.chunks[index]
+    }
+}
+
+impl IntoIterator for PromptPlan {
+    type Item = PromptChunk;
+    type IntoIter = std::vec::IntoIter<PromptChunk>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.chunks.into_iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a PromptPlan {
+    type Item = &'a PromptChunk;
+    type IntoIter = std::slice::Iter<'a, PromptChunk>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.chunks.iter()
+    }
+}
+
+/// One request of a plan.
+#[derive(Debug, Clone)]
+pub struct PromptChunk {
+    /// The messages to send.
+    pub messages: Vec<crate::ChatCompletionRequestMessage>,
+    /// The tokens the messages take, the chat format's overhead included.
+    pub tokens: usize,
+    /// Where the request is in the plan, counting from 0.
+    pub index: usize,
+    /// The number of requests in the plan.
+    pub total: usize,
+    /// The part of each chunkable field that went out in the request, in field order.
+    pub sources: Vec<ChunkSource>,
+}
+
+/// The part of a chunkable field a request covers.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChunkSource {
+    /// The name of the field.
+    pub field: &'static str,
+    pub range: ChunkRange,
+}
+
+/// A part of a field. All ranges count from 0 and leave out their end.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ChunkRange {
+    /// A slice of text, as the bytes and the chars of the field it takes up and the lines those
+    /// are on.
+    Text {
+        bytes: Range<usize>,
+        chars: Range<usize>,
+        lines: Range<usize>,
+    },
+    /// A run of elements of a collection.
+    Elements(Range<usize>),
+}
+
+impl ChunkRange {
+    /// The range of `text` that `bytes` covers.
+    pub fn text(text: &str, bytes: Range<usize>) -> Self {
+        let chars_before = text[..bytes.start].chars().count();
+        let chars = chars_before..chars_before + text[bytes.clone()].chars().count();
+
+        // a line is only counted once the chunk has a byte of it, the newline a chunk ends on
+        // belongs to the line before it
+        let newlines = |end: usize| text.as_bytes()[..end].iter().filter(|&&b| b == b'\n').count();
+        let first_line = newlines(bytes.start);
+        let last_line = match text.as_bytes()[bytes.clone()].last() {
+            Some(b'\n') | None => newlines(bytes.end),
+            Some(_) => newlines(bytes.end) + 1,
+        };
+
+        ChunkRange::Text {
+            bytes,
+            chars,
+            lines: first_line..last_line.max(first_line),
+        }
+    }
+}
+
+/// A #[truncatable] field that was cut short. The cut is the same for every request of a plan.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Truncation {
+    /// The name of the field.
+    pub field: &'static str,
+    /// The bytes of the field's text that were cut out and replaced with an ellipsis.
+    pub removed: Range<usize>,
+    /// The tokens the field's text took before it was cut.
+    pub tokens_before: usize,
+    /// The tokens the field's text takes now, the ellipsis included.
+    pub tokens_after: usize,
+}
+
+/// What sending a prompt would take, worked out without sending it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Estimate {
+    /// The model the prompt was built for.
+    pub model: String,
+    /// The tokens each request takes, in order. There are as many requests as there are counts.
+    pub tokens_per_request: Vec<usize>,
+    /// The tokens of all the requests together.
+    pub total_tokens: usize,
+    /// What the prompt tokens cost in dollars, if the model's price is known. Completions are
+    /// billed on top of this.
+    pub price: Option<f64>,
+}
+
+impl Estimate {
+    /// The estimate for requests that take tokens_per_request tokens each, at input_price dollars
+    /// per million tokens.
+    pub fn new(model: impl Into<String>, tokens_per_request: Vec<usize>, input_price: Option<f64>) -> Self {
+        let total_tokens = tokens_per_request.iter().sum();
+
+        Estimate {
+            model: model.into(),
+            tokens_per_request,
+            total_tokens,
+            price: input_price.map(|price| total_tokens as f64 * price / 1_000_000.0),
+        }
+    }
+
+    /// The number of requests.
+    pub fn requests(&self) -> usize {
+        self.tokens_per_request.len()
+    }
+}
+
+impl std::fmt::Display for Estimate {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}: {} requests, {} prompt tokens",
+            self.model,
+            self.requests(),
+            self.total_tokens
+        )?;
+        match self.price {
+            Some(price) => writeln!(f, ", ${:.4}", price)?,
+            None => writeln!(f, ", price unknown")?,
+        }
+
+        for (i, tokens) in self.tokens_per_request.iter().enumerate() {
+            writeln!(f, "  request {}: {} tokens", i + 1, tokens)?;
+        }
+
+        Ok(())
+    }
+}
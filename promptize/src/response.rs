@@ -0,0 +1,45 @@
+// Prompts that want a JSON answer of a certain shape. The shape is described
+// to the model as a JSON schema, and its answer is parsed back into the type,
+// code fences and all.
+
+use crate::PromptizeError;
+
+/// What a system message asks of the model for it to answer with JSON of type T, with the JSON
+/// schema of T's serde shape.
+pub fn response_instructions<T: schemars::JsonSchema>() -> String {
+    let schema = schemars::schema_for!(T);
+    format!(
+        "Respond only with JSON that matches this JSON schema, without any other text:\n{}",
+        serde_json::to_string(&schema).expect("a JSON schema is always valid JSON")
+    )
+}
+
+/// Parses a model's answer as JSON of type T. Models like to put JSON in a markdown code block,
+/// sometimes with text around it, so the first code block is parsed when there is one.
+pub fn parse_response<T: serde::de::DeserializeOwned>(response: &str) -> Result<T, PromptizeError> {
+    serde_json::from_str(strip_code_fence(response)).map_err(PromptizeError::InvalidResponse)
+}
+
+/// The contents of the first ``` code block in text, or all of the text when there isn't one.
+fn strip_code_fence(text: &str) -> &str {
+    let text = text.trim();
+    let Some(start) = text.find("```") else {
+        return text;
+    };
+
+    let block = &text[start + 3..];
+    let block = &block[..block.find("```").unwrap_or(block.len())];
+
+    // the opening fence can name a language, like ```json, on a line of its own or in front of the
+    // code when the whole block is on one line
+    match block.find('\n') {
+        Some(i) => block[i + 1..].trim(),
+        None => {
+            let language = match block.find(char::is_whitespace) {
+                Some(i) if block[..i].chars().all(|c| c.is_ascii_alphanumeric()) => i,
+                _ => 0,
+            };
+            block[language..].trim()
+        }
+    }
+}
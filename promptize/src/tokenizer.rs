@@ -0,0 +1,419 @@
+// Prompts are measured and cut up in tokens, and what a token is depends on
+// the model. Everything `#[derive(Promptize)]` generates goes through the
+// Tokenizer trait so that any model's tokenizer can be plugged in.
+
+use crate::PromptizeError;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, OnceLock};
+
+/// Turns text into the tokens a model sees and back.
+///
+/// Chunks are cut where the tokens of `token_ends` end. Its default finds them by decoding the
+/// tokens, which only works for tokenizers whose `decode` gives back exactly the text the tokens
+/// were encoded from.
+pub trait Tokenizer {
+    /// The token ids of `text`. Fails with `PromptizeError::Tokenizer` on text the tokenizer can't
+    /// encode, like a char a `HuggingFaceTokenizer` has no token for.
+    fn encode(&self, text: &str) -> Result<Vec<usize>, PromptizeError>;
+
+    /// The text of `tokens`, or `None` if they don't decode to whole chars. That happens to
+    /// byte-level tokenizers when a multibyte char is split over tokens and only some are given.
+    fn decode(&self, tokens: &[usize]) -> Option<String>;
+
+    /// The byte offset in `text` that each of its tokens ends at, or `None` for a token that ends
+    /// inside of a char, which text can't be cut after. The default decodes the tokens one after
+    /// another and fails when they don't decode back to the text.
+    fn token_ends(&self, text: &str) -> Result<Vec<Option<usize>>, PromptizeError> {
+        let tokens = self.encode(text)?;
+        let mut ends = Vec::with_capacity(tokens.len());
+        let mut end = 0;
+        // tokens after the last one that ended on a char boundary, they only decode together
+        let mut from = 0;
+
+        for i in 0..tokens.len() {
+            match self.decode(&tokens[from..=i]) {
+                Some(piece) if text[end..].starts_with(&piece) => {
+                    end += piece.len();
+                    ends.push(Some(end));
+                    from = i + 1;
+                },
+                Some(_) => return Err(PromptizeError::Tokenizer("the tokens don't decode back to the text".to_string())),
+                None => ends.push(None),
+            }
+        }
+
+        Ok(ends)
+    }
+
+    /// The number of tokens `text` takes.
+    fn count(&self, text: &str) -> Result<usize, PromptizeError> {
+        Ok(self.encode(text)?.len())
+    }
+
+    /// The number of tokens `messages` take as a chat request, the chat format's tokens around every
+    /// message and before the reply included. The default counts them the way OpenAI's chat models
+    /// format them.
+    fn count_messages(&self, messages: &[crate::ChatCompletionRequestMessage]) -> Result<usize, PromptizeError> {
+        count_chat_tokens(self, messages, 3, 1, 3)
+    }
+}
+
+/// Counts `messages` the way tiktoken's num_tokens_from_messages does, with any tokenizer and any
+/// chat format's overhead.
+pub(crate) fn count_chat_tokens<T: Tokenizer + ?Sized>(
+    tokenizer: &T,
+    messages: &[crate::ChatCompletionRequestMessage],
+    tokens_per_message: i32,
+    tokens_per_name: i32,
+    tokens_per_reply: i32,
+) -> Result<usize, PromptizeError> {
+    let mut tokens = tokens_per_reply;
+    for message in messages {
+        tokens += tokens_per_message;
+        tokens += tokenizer.count(&message.role)? as i32;
+        tokens += tokenizer.count(&message.content)? as i32;
+
+        if let Some(name) = &message.name {
+            tokens += tokenizer.count(name)? as i32 + tokens_per_name;
+        }
+    }
+
+    Ok(tokens.max(0) as usize)
+}
+
+impl<T: Tokenizer + ?Sized> Tokenizer for &T {
+    fn encode(&self, text: &str) -> Result<Vec<usize>, PromptizeError> {
+        (**self).encode(text)
+    }
+
+    fn decode(&self, tokens: &[usize]) -> Option<String> {
+        (**self).decode(tokens)
+    }
+
+    fn token_ends(&self, text: &str) -> Result<Vec<Option<usize>>, PromptizeError> {
+        (**self).token_ends(text)
+    }
+
+    fn count(&self, text: &str) -> Result<usize, PromptizeError> {
+        (**self).count(text)
+    }
+
+    fn count_messages(&self, messages: &[crate::ChatCompletionRequestMessage]) -> Result<usize, PromptizeError> {
+        (**self).count_messages(messages)
+    }
+}
+
+/// OpenAI's tokenizers, through tiktoken. Cloning one is cheap, clones share the same encoder.
+#[derive(Clone)]
+pub struct TiktokenTokenizer {
+    bpe: Arc<tiktoken_rs::CoreBPE>,
+}
+
+impl TiktokenTokenizer {
+    /// The tokenizer tiktoken has for an OpenAI model name, like "gpt-4".
+    ///
+    /// Building an encoder parses its whole rank table, so each one is only built the first time a
+    /// model that uses it is asked for and is shared from then on, across threads too.
+    pub fn for_model(model: &str) -> Result<Self, PromptizeError> {
+        static ENCODERS: OnceLock<Mutex<HashMap<tiktoken_rs::tokenizer::Tokenizer, Arc<tiktoken_rs::CoreBPE>>>> =
+            OnceLock::new();
+
+        // models that share an encoding, like gpt-4 and gpt-3.5-turbo, share an encoder
+        let encoding = tiktoken_rs::tokenizer::get_tokenizer(model)
+            .ok_or_else(|| PromptizeError::UnknownModel(model.to_string()))?;
+
+        // the lock is held while an encoder is built so that threads asking for the same one
+        // wait for it instead of building it again. Nothing panics while it is held, but a
+        // poisoned cache is still a valid one.
+        let mut encoders = ENCODERS
+            .get_or_init(Default::default)
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+
+        if let Some(bpe) = encoders.get(&encoding) {
+            return Ok(TiktokenTokenizer { bpe: bpe.clone() });
+        }
+
+        let bpe = tiktoken_rs::get_bpe_from_tokenizer(encoding)
+            .map_err(|_| PromptizeError::UnknownModel(model.to_string()))?;
+        let bpe = Arc::new(bpe);
+        encoders.insert(encoding, bpe.clone());
+
+        Ok(TiktokenTokenizer { bpe })
+    }
+}
+
+impl From<tiktoken_rs::CoreBPE> for TiktokenTokenizer {
+    fn from(bpe: tiktoken_rs::CoreBPE) -> Self {
+        TiktokenTokenizer { bpe: Arc::new(bpe) }
+    }
+}
+
+impl Tokenizer for TiktokenTokenizer {
+    fn encode(&self, text: &str) -> Result<Vec<usize>, PromptizeError> {
+        Ok(self.bpe.encode_with_special_tokens(text))
+    }
+
+    fn decode(&self, tokens: &[usize]) -> Option<String> {
+        self.bpe.decode(tokens.to_vec()).ok()
+    }
+}
+
+/// A tokenizer loaded from a HuggingFace `tokenizer.json`, for local models that don't use
+/// OpenAI's tokenizers. Chunks are cut on the offsets the tokenizer gives each token in the text,
+/// so tokenizers that normalize the text on the way in can cut them up too.
+///
+/// Encoding fails on text the tokenizer's model can't encode, like a char a BPE without byte-level
+/// tokens has no token for. Byte-level tokenizers can encode any text.
+#[cfg(feature = "huggingface")]
+pub struct HuggingFaceTokenizer {
+    tokenizer: tokenizers::Tokenizer,
+}
+
+#[cfg(feature = "huggingface")]
+impl HuggingFaceTokenizer {
+    /// Loads a `tokenizer.json` from disk.
+    pub fn from_file(path: impl AsRef<std::path::Path>) -> Result<Self, PromptizeError> {
+        let path = path.as_ref();
+        let tokenizer = tokenizers::Tokenizer::from_file(path)
+            .map_err(|error| PromptizeError::Tokenizer(format!("can't load {}: {}", path.display(), error)))?;
+
+        Ok(HuggingFaceTokenizer { tokenizer })
+    }
+}
+
+#[cfg(feature = "huggingface")]
+impl From<tokenizers::Tokenizer> for HuggingFaceTokenizer {
+    fn from(tokenizer: tokenizers::Tokenizer) -> Self {
+        HuggingFaceTokenizer { tokenizer }
+    }
+}
+
+#[cfg(feature = "huggingface")]
+impl HuggingFaceTokenizer {
+    fn encoding(&self, text: &str) -> Result<tokenizers::Encoding, PromptizeError> {
+        // byte-level models encode anything, other models fail on chars they have no token for
+        self.tokenizer
+            .encode(text, false)
+            .map_err(|error| PromptizeError::Tokenizer(format!("can't encode the text: {}", error)))
+    }
+}
+
+#[cfg(feature = "huggingface")]
+impl Tokenizer for HuggingFaceTokenizer {
+    fn encode(&self, text: &str) -> Result<Vec<usize>, PromptizeError> {
+        Ok(self.encoding(text)?.get_ids().iter().map(|&id| id as usize).collect())
+    }
+
+    fn token_ends(&self, text: &str) -> Result<Vec<Option<usize>>, PromptizeError> {
+        let encoding = self.encoding(text)?;
+        let offsets = encoding.get_offsets();
+
+        // byte-level models give every token of a char that is split over several the offsets of
+        // the whole char, so a token ends inside of a char when the next one starts before it ends
+        let ends = offsets
+            .iter()
+            .enumerate()
+            .map(|(i, &(_, end))| match offsets.get(i + 1) {
+                Some(&(next_start, _)) if next_start < end => None,
+                _ => Some(end),
+            })
+            .collect();
+
+        Ok(ends)
+    }
+
+    fn decode(&self, tokens: &[usize]) -> Option<String> {
+        let ids = tokens.iter().map(|&id| id as u32).collect::<Vec<u32>>();
+        let text = self.tokenizer.decode(&ids, false).ok()?;
+
+        // byte-level tokenizers decode half a char as the replacement char, at the end of the first
+        // tokens of a text or at the start of the last ones
+        match text.ends_with(char::REPLACEMENT_CHARACTER) || text.starts_with(char::REPLACEMENT_CHARACTER) {
+            true => None,
+            false => Some(text),
+        }
+    }
+}
+
+/// Estimates tokens without a model's vocabulary, for models whose tokenizer isn't available.
+///
+/// Text is split into words, each with the whitespace before it, and words into runs of up to
+/// `chars_per_token` chars. English averages about 4 chars a token with OpenAI's tokenizers, which
+/// is the default.
+pub struct HeuristicTokenizer {
+    chars_per_token: usize,
+}
+
+impl HeuristicTokenizer {
+    /// A token for every `chars_per_token` chars of a word, between 1 and 7, or 3 on 32-bit targets.
+    pub fn new(chars_per_token: usize) -> Self {
+        HeuristicTokenizer {
+            chars_per_token: chars_per_token.clamp(1, MAX_TOKEN_BYTES),
+        }
+    }
+}
+
+impl Default for HeuristicTokenizer {
+    fn default() -> Self {
+        HeuristicTokenizer::new(4)
+    }
+}
+
+/// The bytes of a token are packed into its id, with their count in the top byte.
+const MAX_TOKEN_BYTES: usize = std::mem::size_of::<usize>() - 1;
+
+/// Where the count of a token's bytes goes in its id.
+const LEN_SHIFT: u32 = usize::BITS - 8;
+
+impl Tokenizer for HeuristicTokenizer {
+    fn encode(&self, text: &str) -> Result<Vec<usize>, PromptizeError> {
+        let mut tokens = vec![];
+        let mut token: Vec<u8> = vec![];
+        let mut chars = 0;
+        let mut prev_whitespace = false;
+
+        for c in text.chars() {
+            let whitespace = c.is_whitespace();
+            let len = c.len_utf8();
+
+            // the whitespace before a word goes in a token with the start of the word
+            let word_end = whitespace && !prev_whitespace;
+            let full = chars == self.chars_per_token || token.len() + len > MAX_TOKEN_BYTES;
+
+            if !token.is_empty() && (word_end || full) {
+                tokens.push(pack(&token));
+                token.clear();
+                chars = 0;
+            }
+
+            // on 32-bit targets a 4 byte char doesn't fit in a token and goes on in the next
+            let mut buf = [0; 4];
+            for &byte in c.encode_utf8(&mut buf).as_bytes() {
+                if token.len() == MAX_TOKEN_BYTES {
+                    tokens.push(pack(&token));
+                    token.clear();
+                }
+                token.push(byte);
+            }
+            chars += 1;
+            prev_whitespace = whitespace;
+        }
+
+        if !token.is_empty() {
+            tokens.push(pack(&token));
+        }
+
+        Ok(tokens)
+    }
+
+    fn decode(&self, tokens: &[usize]) -> Option<String> {
+        let bytes = tokens.iter().flat_map(|&token| unpack(token)).collect::<Vec<u8>>();
+        String::from_utf8(bytes).ok()
+    }
+}
+
+fn pack(bytes: &[u8]) -> usize {
+    let mut id = bytes.len() << LEN_SHIFT;
+    for (i, &byte) in bytes.iter().enumerate() {
+        id |= (byte as usize) << (i * 8);
+    }
+    id
+}
+
+fn unpack(id: usize) -> Vec<u8> {
+    let len = id >> LEN_SHIFT;
+    (0..len.min(MAX_TOKEN_BYTES)).map(|i| (id >> (i * 8)) as u8).collect()
+}
+
+#[cfg(all(test, feature = "huggingface"))]
+mod tests {
+    use super::*;
+    use crate::{chunk_text, SplitStrategy};
+
+    fn tokenizer() -> HuggingFaceTokenizer {
+        HuggingFaceTokenizer::from_file(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/byte-level-tokenizer.json"))
+            .unwrap()
+    }
+
+    // lowercases the text and drops the whitespace
+    fn lowercase_tokenizer() -> HuggingFaceTokenizer {
+        HuggingFaceTokenizer::from_file(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/lowercase-tokenizer.json"))
+            .unwrap()
+    }
+
+    #[test]
+    fn decoding_gives_back_the_text() {
+        let tokenizer = tokenizer();
+        let text = "the fox and the lazy dog, é и 日本語 🦀";
+
+        let tokens = tokenizer.encode(text).unwrap();
+        assert!(tokens.len() < text.len());
+        assert_eq!(tokenizer.decode(&tokens).as_deref(), Some(text));
+
+        // every prefix decodes to a prefix of the text, or not at all when it ends in half a char
+        for size in 0..tokens.len() {
+            if let Some(prefix) = tokenizer.decode(&tokens[..size]) {
+                assert!(text.starts_with(&prefix));
+            }
+        }
+        assert_eq!(tokenizer.decode(&tokenizer.encode("🦀").unwrap()[..1]), None);
+
+        // and text can only be cut after the tokens that end on a char boundary
+        let ends = tokenizer.token_ends("a🦀").unwrap();
+        assert_eq!(ends.first(), Some(&Some(1)));
+        assert_eq!(ends.last(), Some(&Some(5)));
+        assert!(ends[1..ends.len() - 1].iter().all(Option::is_none));
+    }
+
+    #[test]
+    fn chunks_cover_the_text() {
+        let tokenizer = tokenizer();
+        let text = "the quick fox jumps over the lazy dog. é и 日本語のテキスト 🦀🚀\n".repeat(40);
+
+        let chunks = chunk_text(&text, &tokenizer, 25, SplitStrategy::Tokens).unwrap();
+
+        assert!(chunks.len() > 1);
+        assert_eq!(chunks.concat(), text);
+        for chunk in &chunks {
+            assert!(tokenizer.count(chunk).unwrap() <= 25);
+        }
+    }
+
+    #[test]
+    fn text_is_cut_where_the_tokens_of_a_normalizing_tokenizer_end() {
+        let tokenizer = lowercase_tokenizer();
+        let text = "The Quick Brown Fox jumps over the Lazy Dog.  ".repeat(20);
+
+        // the tokens decode to other text than they were encoded from
+        let tokens = tokenizer.encode(&text).unwrap();
+        assert_ne!(tokenizer.decode(&tokens).as_deref(), Some(text.as_str()));
+
+        let chunks = chunk_text(&text, &tokenizer, 25, SplitStrategy::Tokens).unwrap();
+
+        assert!(chunks.len() > 1);
+        assert_eq!(chunks.concat(), text);
+        for chunk in &chunks {
+            assert!(tokenizer.count(chunk).unwrap() <= 25);
+        }
+    }
+
+    #[test]
+    fn text_a_tokenizer_cant_encode_is_an_error() {
+        // a model without a vocabulary or the unknown token to fall back on can't encode anything
+        let model = tokenizers::models::wordlevel::WordLevel::default();
+        let tokenizer = HuggingFaceTokenizer::from(tokenizers::Tokenizer::new(model));
+
+        assert!(matches!(tokenizer.encode("the cat"), Err(PromptizeError::Tokenizer(_))));
+        assert!(matches!(tokenizer.count("the cat"), Err(PromptizeError::Tokenizer(_))));
+
+        let result = chunk_text("the quick cat", &tokenizer, 1, SplitStrategy::Tokens);
+        assert!(matches!(result, Err(PromptizeError::Tokenizer(_))));
+    }
+
+    #[test]
+    fn a_missing_file_is_an_error() {
+        let result = HuggingFaceTokenizer::from_file("no/such/tokenizer.json");
+        assert!(matches!(result, Err(PromptizeError::Tokenizer(_))));
+    }
+}
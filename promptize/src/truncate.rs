@@ -0,0 +1,159 @@
+// Cutting text short for #[truncatable] fields. When the fields that aren't
+// chunked take so much of a prompt that there is no room left for a chunk,
+// the truncatable ones are cut down, lowest priority first, before
+// build_prompt gives up on the budget.
+
+use crate::{PromptizeError, Tokenizer};
+use std::ops::Range;
+
+/// Where the ellipsis goes in text that is cut short, see #[truncatable(ellipsis = "...")]. The
+/// rest of the text is what is kept.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Ellipsis {
+    /// Keeps the end of the text.
+    Head,
+    /// Keeps the start of the text.
+    #[default]
+    Tail,
+    /// Keeps the start and the end of the text, half of the tokens each.
+    Middle,
+}
+
+/// What stands in for the part of a text that was cut.
+pub const ELLIPSIS: &str = "…";
+
+/// Cuts text down to at most about `tokens` tokens, the ellipsis included, and returns it along
+/// with the byte range of the text that was cut out. Text that fits comes back whole with an
+/// empty range, and text that only has room for the ellipsis comes back empty.
+///
+/// Text is only cut on token boundaries that are also char boundaries. The ellipsis can merge
+/// with the tokens next to it, so the cut text is worth counting again where it has to fit.
+pub fn truncate_text<T: Tokenizer + ?Sized>(
+    text: &str,
+    tokenizer: &T,
+    tokens: usize,
+    ellipsis: Ellipsis,
+) -> Result<(String, Range<usize>), PromptizeError> {
+    if tokenizer.count(text)? <= tokens {
+        return Ok((text.to_string(), text.len()..text.len()));
+    }
+
+    let room = tokens.saturating_sub(tokenizer.count(ELLIPSIS)?);
+    if room == 0 {
+        return Ok((String::new(), 0..text.len()));
+    }
+
+    let token_ends = tokenizer.token_ends(text)?;
+
+    // BPE tokens are bytes, so a cut can land inside of a multibyte char. The head backs off a
+    // token in that case and the tail starts a token later.
+    let head_end = |size: usize| {
+        (0..=size.min(token_ends.len()))
+            .rev()
+            .find_map(|size| match size {
+                0 => Some(0),
+                size => token_ends[size - 1],
+            })
+            .unwrap_or(0)
+    };
+    let tail_start = |size: usize| {
+        (token_ends.len().saturating_sub(size)..token_ends.len())
+            .find_map(|start| match start {
+                0 => Some(0),
+                start => token_ends[start - 1],
+            })
+            .unwrap_or(text.len())
+    };
+
+    let cut = match ellipsis {
+        Ellipsis::Head => 0..tail_start(room),
+        Ellipsis::Tail => head_end(room)..text.len(),
+        Ellipsis::Middle => {
+            let start = head_end(room - room / 2);
+            let end = tail_start(room / 2);
+            start..end.max(start)
+        }
+    };
+
+    let truncated = format!("{}{}{}", &text[..cut.start], ELLIPSIS, &text[cut.end..]);
+    Ok((truncated, cut))
+}
+
+/// Adds the tokens a prompt is short by to the cuts of the truncatable fields, given in the
+/// order they are cut in along with the tokens each takes. Each field is cut as far as it goes
+/// before the next one is. Returns false when there was nothing left to cut.
+pub fn add_cuts(cuts: &mut [usize], field_tokens: &[usize], mut short: usize) -> bool {
+    let mut cut_any = false;
+
+    for (cut, &tokens) in cuts.iter_mut().zip(field_tokens) {
+        if short == 0 {
+            break;
+        }
+
+        let more = short.min(tokens.saturating_sub(*cut));
+        *cut += more;
+        short -= more;
+        cut_any |= more > 0;
+    }
+
+    cut_any
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{HeuristicTokenizer, TiktokenTokenizer};
+
+    #[test]
+    fn the_ellipsis_goes_where_the_text_is_cut() {
+        let tokenizer = TiktokenTokenizer::for_model("gpt-4").unwrap();
+        let text = (0..200).map(|i| format!("word{} ", i)).collect::<String>();
+
+        let (tail, cut) = truncate_text(&text, &tokenizer, 50, Ellipsis::Tail).unwrap();
+        assert!(tail.starts_with("word0 ") && tail.ends_with(ELLIPSIS));
+        assert_eq!(cut.end, text.len());
+
+        let (head, cut) = truncate_text(&text, &tokenizer, 50, Ellipsis::Head).unwrap();
+        assert!(head.starts_with(ELLIPSIS) && head.ends_with("word199 "));
+        assert_eq!(cut.start, 0);
+
+        let (middle, cut) = truncate_text(&text, &tokenizer, 50, Ellipsis::Middle).unwrap();
+        assert!(middle.starts_with("word0 ") && middle.ends_with("word199 "));
+        assert_eq!(middle, format!("{}{}{}", &text[..cut.start], ELLIPSIS, &text[cut.end..]));
+
+        for truncated in [tail, head, middle] {
+            assert!(tokenizer.count(&truncated).unwrap() <= 52);
+        }
+    }
+
+    #[test]
+    fn multibyte_chars_are_not_cut_in_half() {
+        let tokenizer = TiktokenTokenizer::for_model("gpt-4").unwrap();
+        let text = "日本語のテキスト 🦀🚀 ".repeat(50);
+
+        for ellipsis in [Ellipsis::Head, Ellipsis::Tail, Ellipsis::Middle] {
+            let (truncated, cut) = truncate_text(&text, &tokenizer, 21, ellipsis).unwrap();
+            assert!(text.is_char_boundary(cut.start) && text.is_char_boundary(cut.end));
+            assert!(truncated.contains(ELLIPSIS));
+        }
+    }
+
+    #[test]
+    fn text_that_fits_is_not_cut() {
+        let tokenizer = HeuristicTokenizer::default();
+        assert_eq!(truncate_text("short", &tokenizer, 10, Ellipsis::Tail).unwrap(), ("short".to_string(), 5..5));
+        assert_eq!(truncate_text("not short at all", &tokenizer, 1, Ellipsis::Tail).unwrap(), (String::new(), 0..16));
+    }
+
+    #[test]
+    fn cuts_use_up_one_field_before_the_next() {
+        let mut cuts = [0, 0, 0];
+        assert!(add_cuts(&mut cuts, &[10, 20, 30], 15));
+        assert_eq!(cuts, [10, 5, 0]);
+
+        assert!(add_cuts(&mut cuts, &[10, 20, 30], 50));
+        assert_eq!(cuts, [10, 20, 30]);
+
+        assert!(!add_cuts(&mut cuts, &[10, 20, 30], 1));
+    }
+}
@@ -2,6 +2,8 @@ use promptize::Promptize;
 
 #[derive(Promptize)]
 pub struct FileContent {
+    system_prompt: String,
+    user_prompt: String,
     pub filename: String,
     #[chunkable]
     pub file_content: String
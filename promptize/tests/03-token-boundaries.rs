@@ -0,0 +1,52 @@
+// Chunks are cut on tiktoken token boundaries rather than on a character count estimated from
+// the ratio of tokens to chars. Code and multibyte text tokenize very differently from prose, so
+// both are used here to make sure every emitted request still fits inside of the token limit.
+
+use promptize::Promptize;
+
+#[derive(Promptize)]
+pub struct FileContent {
+    system_prompt: String,
+    user_prompt: String,
+    pub filename: String,
+    #[chunkable]
+    pub file_content: String
+}
+
+fn main() {
+    let bpe = tiktoken_rs::get_bpe_from_model("gpt-4").unwrap();
+    let token_limit = 1000;
+
+    let code = "fn main() {\n    let x: Vec<u8> = vec![0xde, 0xad];\n    println!(\"{:?}\", x);\n}\n".repeat(200);
+    let multibyte = "日本語のテキスト、émojis 🦀🚀 и кириллица. ".repeat(200);
+
+    for content in [code, multibyte] {
+        let mut builder = FileContent::builder();
+        builder
+            .system_prompt("You are a computer system that responds only in JSON.".to_string())
+            .user_prompt("Summarize this file.".to_string())
+            .filename("huge_file.rs".to_string())
+            .file_content(content.clone());
+
+        let prompts = builder.build_prompt("gpt-4", token_limit, 200).unwrap();
+        assert!(prompts.len() > 1);
+
+        for prompt in &prompts {
+            let tokens: usize = prompt.messages
+                .iter()
+                .map(|m| bpe.encode_with_special_tokens(&m.content).len())
+                .sum();
+            assert!(tokens <= token_limit as usize);
+        }
+
+        // nothing is lost or mangled at the chunk boundaries
+        let joined = prompts
+            .iter()
+            .map(|p| {
+                let user: serde_json::Value = serde_json::from_str(&p.messages.last().unwrap().content).unwrap();
+                user["file_content"].as_str().unwrap().to_string()
+            })
+            .collect::<String>();
+        assert_eq!(joined, content);
+    }
+}
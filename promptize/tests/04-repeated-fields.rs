@@ -0,0 +1,39 @@
+// Only the #[chunkable] field is split up. Every other field is repeated in each chunk so that
+// every request the model receives is valid JSON and makes sense on its own.
+
+use promptize::Promptize;
+
+#[derive(Promptize)]
+pub struct FileContent {
+    system_prompt: String,
+    user_prompt: String,
+    pub filename: String,
+    #[chunkable]
+    pub file_content: String
+}
+
+fn main() {
+    let content = "The quick brown fox jumps over the lazy dog.\n".repeat(500);
+
+    let prompts = FileContent::builder()
+        .system_prompt("You are a computer system that responds only in JSON.".to_string())
+        .user_prompt("Summarize this file.".to_string())
+        .filename("huge_file.txt".to_string())
+        .file_content(content.clone())
+        .build_prompt("gpt-4", 1000, 200)
+        .unwrap();
+
+    assert!(prompts.len() > 1);
+
+    let mut joined = String::new();
+    for prompt in &prompts {
+        assert_eq!(prompt.messages[0].content, "You are a computer system that responds only in JSON.");
+        assert_eq!(prompt.messages[1].content, "Summarize this file.");
+
+        let user: serde_json::Value = serde_json::from_str(&prompt.messages[2].content).unwrap();
+        assert_eq!(user["filename"], "huge_file.txt");
+        joined.push_str(user["file_content"].as_str().unwrap());
+    }
+
+    assert_eq!(joined, content);
+}
@@ -0,0 +1,66 @@
+// #[chunkable(overlap = N)] repeats the last N tokens of each chunk at the start of the next one
+// so that context at a chunk boundary is not lost. The overlap counts against the token limit.
+
+use promptize::Promptize;
+
+#[derive(Promptize)]
+pub struct FileContent {
+    system_prompt: String,
+    user_prompt: String,
+    pub filename: String,
+    #[chunkable(overlap = 50)]
+    pub file_content: String
+}
+
+fn main() {
+    let bpe = tiktoken_rs::get_bpe_from_model("gpt-4").unwrap();
+    let token_limit = 1000;
+
+    let content = (0..600)
+        .map(|i| format!("line {} of the file says something about item {}\n", i, i * 7))
+        .collect::<String>();
+
+    let prompts = FileContent::builder()
+        .system_prompt("You are a computer system that responds only in JSON.".to_string())
+        .user_prompt("Summarize this file.".to_string())
+        .filename("huge_file.txt".to_string())
+        .file_content(content.clone())
+        .build_prompt("gpt-4", token_limit, 200)
+        .unwrap();
+
+    assert!(prompts.len() > 1);
+
+    let chunks = prompts
+        .iter()
+        .map(|prompt| {
+            let tokens: usize = prompt.messages
+                .iter()
+                .map(|m| bpe.encode_with_special_tokens(&m.content).len())
+                .sum();
+            assert!(tokens <= token_limit as usize);
+
+            let user: serde_json::Value = serde_json::from_str(&prompt.messages.last().unwrap().content).unwrap();
+            user["file_content"].as_str().unwrap().to_string()
+        })
+        .collect::<Vec<_>>();
+
+    // every chunk starts with the tail of the one before it, and dropping those repeated tails
+    // gives back the original text
+    let mut joined = chunks[0].clone();
+    for pair in chunks.windows(2) {
+        let (prev, next) = (&pair[0], &pair[1]);
+        let repeated = next
+            .char_indices()
+            .map(|(i, _)| i)
+            .chain(std::iter::once(next.len()))
+            .filter(|&i| prev.ends_with(&next[..i]))
+            .max()
+            .unwrap();
+
+        assert!(repeated > 0);
+        assert!(bpe.encode_with_special_tokens(&next[..repeated]).len() <= 60);
+        joined.push_str(&next[repeated..]);
+    }
+
+    assert_eq!(joined, content);
+}
@@ -0,0 +1,52 @@
+// #[chunkable(split = "lines")] only cuts a chunk at the end of a line. A line too long to fit in
+// a chunk on its own is the one case where it falls back to cutting between tokens.
+
+use promptize::Promptize;
+
+#[derive(Promptize)]
+pub struct FileContent {
+    system_prompt: String,
+    user_prompt: String,
+    pub filename: String,
+    #[chunkable(split = "lines")]
+    pub file_content: String
+}
+
+fn main() {
+    let bpe = tiktoken_rs::get_bpe_from_model("gpt-4").unwrap();
+    let token_limit = 1000;
+
+    let lines = |from: usize| {
+        (from..from + 300)
+            .map(|i| format!("line {} of the file says something about item {}\n", i, i * 7))
+            .collect::<String>()
+    };
+    let huge_line = format!("{}\n", "word ".repeat(3000));
+    let content = format!("{}{}{}", lines(0), huge_line, lines(300));
+
+    let prompts = FileContent::builder()
+        .system_prompt("You are a computer system that responds only in JSON.".to_string())
+        .user_prompt("Summarize this file.".to_string())
+        .filename("huge_file.txt".to_string())
+        .file_content(content.clone())
+        .build_prompt("gpt-4", token_limit, 200)
+        .unwrap();
+
+    let mut joined = String::new();
+    for prompt in &prompts {
+        let tokens: usize = prompt.messages
+            .iter()
+            .map(|m| bpe.encode_with_special_tokens(&m.content).len())
+            .sum();
+        assert!(tokens <= token_limit as usize);
+
+        let user: serde_json::Value = serde_json::from_str(&prompt.messages.last().unwrap().content).unwrap();
+        let chunk = user["file_content"].as_str().unwrap();
+
+        // either whole lines, or a piece out of the middle of the huge line
+        assert!(chunk.ends_with('\n') || !chunk.contains('\n'));
+        joined.push_str(chunk);
+    }
+
+    assert_eq!(joined, content);
+}
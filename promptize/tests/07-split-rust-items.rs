@@ -0,0 +1,44 @@
+// #[chunkable(split = "rust_items")] parses the field with syn and keeps whole items together, so
+// every chunk of a rust file is valid rust on its own as long as no single item is too big.
+
+use promptize::Promptize;
+
+#[derive(Promptize)]
+pub struct FileContent {
+    system_prompt: String,
+    user_prompt: String,
+    pub filename: String,
+    #[chunkable(split = "rust_items")]
+    pub file_content: String
+}
+
+fn main() {
+    let content = (0..60)
+        .map(|i| {
+            format!(
+                "/// Does thing number {i}\nfn thing_{i}(x: u32) -> u32 {{\n    let y = x * {i};\n    y + 1\n}}\n\nstruct Thing{i} {{\n    value: u32,\n}}\n\nimpl Thing{i} {{\n    fn value(&self) -> u32 {{\n        self.value\n    }}\n}}\n\n"
+            )
+        })
+        .collect::<String>();
+
+    let prompts = FileContent::builder()
+        .system_prompt("You are a computer system that responds only in JSON.".to_string())
+        .user_prompt("Review this file.".to_string())
+        .filename("huge_file.rs".to_string())
+        .file_content(content.clone())
+        .build_prompt("gpt-4", 1000, 200)
+        .unwrap();
+
+    assert!(prompts.len() > 1);
+
+    let mut joined = String::new();
+    for prompt in &prompts {
+        let user: serde_json::Value = serde_json::from_str(&prompt.messages.last().unwrap().content).unwrap();
+        let chunk = user["file_content"].as_str().unwrap();
+
+        assert!(syn::parse_file(chunk).is_ok());
+        joined.push_str(chunk);
+    }
+
+    assert_eq!(joined, content);
+}
@@ -0,0 +1,14 @@
+// The split strategy is checked at compile time, pointing at the string that was given.
+
+use promptize::Promptize;
+
+#[derive(Promptize)]
+pub struct FileContent {
+    system_prompt: String,
+    user_prompt: String,
+    pub filename: String,
+    #[chunkable(split = "words")]
+    pub file_content: String
+}
+
+fn main() {}
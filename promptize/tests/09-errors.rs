@@ -0,0 +1,41 @@
+// build_prompt fails with a PromptizeError that callers can match on, for example to retry with a
+// bigger model when the budget is too small.
+
+use promptize::{Promptize, PromptizeError};
+
+#[derive(Promptize)]
+pub struct FileContent {
+    system_prompt: String,
+    user_prompt: String,
+    pub filename: String,
+    #[chunkable]
+    pub file_content: String
+}
+
+fn main() {
+    let mut builder = FileContent::builder();
+    builder
+        .system_prompt("You are a computer system that responds only in JSON.".to_string())
+        .user_prompt("Summarize this file.".to_string())
+        .filename("huge_file.txt".to_string());
+
+    match builder.build_prompt("gpt-4", 10, 5) {
+        Err(PromptizeError::MissingField(field)) => assert_eq!(field, "file_content"),
+        other => panic!("expected MissingField, got {:?}", other),
+    }
+
+    builder.file_content("The quick brown fox jumps over the lazy dog.\n".repeat(500));
+
+    match builder.build_prompt("not-a-model", 1000, 200) {
+        Err(PromptizeError::UnknownModel(model)) => assert_eq!(model, "not-a-model"),
+        other => panic!("expected UnknownModel, got {:?}", other),
+    }
+
+    match builder.build_prompt("gpt-4", 1000, 990) {
+        Err(PromptizeError::BudgetTooSmall { available, required }) => {
+            assert!(available < required);
+            assert_eq!(required, 990);
+        },
+        other => panic!("expected BudgetTooSmall, got {:?}", other),
+    }
+}
@@ -0,0 +1,40 @@
+// Required fields that were never set come back as a MissingField error instead of a panic.
+// #[promptize(default = "...")] gives a field a value to fall back on when it is not set.
+
+use promptize::{Promptize, PromptizeError};
+
+#[derive(Promptize)]
+pub struct FileContent {
+    #[promptize(default = "You are a computer system that responds only in JSON.")]
+    system_prompt: String,
+    user_prompt: String,
+    pub filename: String,
+    #[chunkable]
+    pub file_content: String
+}
+
+fn main() {
+    let mut builder = FileContent::builder();
+    builder
+        .filename("main.rs".to_string())
+        .file_content("fn main() {}".to_string());
+
+    match builder.build_prompt("gpt-4", 1000, 200) {
+        Err(PromptizeError::MissingField(field)) => assert_eq!(field, "user_prompt"),
+        other => panic!("expected MissingField, got {:?}", other),
+    }
+
+    let prompts = builder
+        .user_prompt("Summarize this file.".to_string())
+        .build_prompt("gpt-4", 1000, 200)
+        .unwrap();
+
+    assert_eq!(prompts[0].messages[0].content, "You are a computer system that responds only in JSON.");
+
+    let prompts = builder
+        .system_prompt("You are a code reviewer.".to_string())
+        .build_prompt("gpt-4", 1000, 200)
+        .unwrap();
+
+    assert_eq!(prompts[0].messages[0].content, "You are a code reviewer.");
+}
@@ -0,0 +1,53 @@
+// #[role(system)], #[role(user)] and #[role(assistant)] send a field of any name as a chat message
+// of its own. Several fields can share a role and the messages come out in field order, followed
+// by the rest of the fields as json.
+
+use promptize::Promptize;
+
+#[derive(Promptize)]
+pub struct Review {
+    #[role(system)]
+    instructions: String,
+    #[role(user)]
+    context: String,
+    #[role(assistant)]
+    acknowledgement: Option<String>,
+    #[role(user)]
+    question: String,
+    pub filename: String,
+    #[chunkable]
+    pub file_content: String
+}
+
+fn main() {
+    let mut builder = Review::builder();
+    builder
+        .instructions("You review rust code.".to_string())
+        .context("The code is part of a web server.".to_string())
+        .question("Is this code correct?".to_string())
+        .filename("main.rs".to_string())
+        .file_content("fn main() {}".to_string());
+
+    let prompts = builder.build_prompt("gpt-4", 1000, 200).unwrap();
+    let roles = prompts[0].messages.iter().map(|m| m.role.as_str()).collect::<Vec<_>>();
+    assert_eq!(roles, ["system", "user", "user", "user"]);
+    assert_eq!(prompts[0].messages[1].content, "The code is part of a web server.");
+
+    let prompts = builder
+        .acknowledgement("Understood.".to_string())
+        .file_content("fn main() {}\n".repeat(2000))
+        .build_prompt("gpt-4", 1000, 200)
+        .unwrap();
+
+    assert!(prompts.len() > 1);
+    for prompt in &prompts {
+        let roles = prompt.messages.iter().map(|m| m.role.as_str()).collect::<Vec<_>>();
+        assert_eq!(roles, ["system", "user", "assistant", "user", "user"]);
+        assert_eq!(prompt.messages[2].content, "Understood.");
+        assert_eq!(prompt.messages[3].content, "Is this code correct?");
+
+        let user: serde_json::Value = serde_json::from_str(&prompt.messages[4].content).unwrap();
+        assert_eq!(user["filename"], "main.rs");
+        assert!(user.get("instructions").is_none());
+    }
+}
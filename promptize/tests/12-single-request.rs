@@ -0,0 +1,42 @@
+// A prompt that fits in one request still sends every field. The fields without a role go out as
+// json in the last user message, the same way they do in every chunk of a chunked prompt.
+
+use promptize::Promptize;
+
+#[derive(Promptize)]
+pub struct FileContent {
+    system_prompt: String,
+    user_prompt: String,
+    pub filename: String,
+    pub language: Option<String>,
+    #[chunkable]
+    pub file_content: String
+}
+
+fn main() {
+    let prompts = FileContent::builder()
+        .system_prompt("You are a computer system that responds only in JSON.".to_string())
+        .user_prompt("Summarize this file.".to_string())
+        .filename("main.rs".to_string())
+        .language("rust".to_string())
+        .file_content("fn main() {\n    println!(\"hello\");\n}\n".to_string())
+        .build_prompt("gpt-4", 1000, 200)
+        .unwrap();
+
+    assert_eq!(prompts.len(), 1);
+
+    let prompt = &prompts[0];
+    assert_eq!(prompt.messages.len(), 3);
+    assert_eq!(prompt.messages[0].content, "You are a computer system that responds only in JSON.");
+    assert_eq!(prompt.messages[1].content, "Summarize this file.");
+
+    let user: serde_json::Value = serde_json::from_str(&prompt.messages[2].content).unwrap();
+    assert_eq!(
+        user,
+        serde_json::json!({
+            "filename": "main.rs",
+            "language": "rust",
+            "file_content": "fn main() {\n    println!(\"hello\");\n}\n",
+        })
+    );
+}
@@ -0,0 +1,48 @@
+// #[promptize(template = "...")] replaces the json of the fields without a role with a template
+// filled in from the fields. When the prompt is chunked, each chunk goes in the placeholder of the
+// chunkable field and the rest of the template is repeated around it.
+
+use promptize::Promptize;
+
+#[derive(Promptize)]
+#[promptize(template = "Analyze {filename} ({lines} lines):\n{file_content}\n{{end}}")]
+pub struct FileContent {
+    system_prompt: String,
+    pub filename: String,
+    pub lines: usize,
+    #[chunkable(split = "lines")]
+    pub file_content: String
+}
+
+fn main() {
+    let mut builder = FileContent::builder();
+    builder
+        .system_prompt("You are a code reviewer.".to_string())
+        .filename("main.rs".to_string())
+        .lines(1)
+        .file_content("fn main() {}".to_string());
+
+    let prompts = builder.build_prompt("gpt-4", 1000, 200).unwrap();
+    assert_eq!(prompts[0].messages[1].content, "Analyze main.rs (1 lines):\nfn main() {}\n{end}");
+
+    let content = "fn main() {}\n".repeat(2000);
+    let prompts = builder
+        .lines(2000)
+        .file_content(content.clone())
+        .build_prompt("gpt-4", 1000, 200)
+        .unwrap();
+
+    assert!(prompts.len() > 1);
+
+    let mut joined = String::new();
+    for prompt in &prompts {
+        let user = &prompt.messages[1].content;
+        let chunk = user
+            .strip_prefix("Analyze main.rs (2000 lines):\n")
+            .and_then(|rest| rest.strip_suffix("\n{end}"))
+            .unwrap();
+        joined.push_str(chunk);
+    }
+
+    assert_eq!(joined, content);
+}
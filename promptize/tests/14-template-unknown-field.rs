@@ -0,0 +1,14 @@
+// Every placeholder in a template has to name a field of the struct.
+
+use promptize::Promptize;
+
+#[derive(Promptize)]
+#[promptize(template = "Analyze {file_name}:\n{file_content}")]
+pub struct FileContent {
+    system_prompt: String,
+    pub filename: String,
+    #[chunkable]
+    pub file_content: String
+}
+
+fn main() {}
@@ -0,0 +1,91 @@
+// More than one field can be #[chunkable]. The token budget left after the other fields is shared
+// between them: fields with a higher `priority` are budgeted first and fields of the same priority
+// split what is left by `weight`. A field that needs less than its share only takes what it needs.
+//
+// With the default zip layout the nth chunk of every field goes out in the same request, so each
+// field is cut into the same number of chunks. Fields that fit whole in their share of the budget
+// are not cut at all and go out whole in every request.
+
+use promptize::Promptize;
+
+#[derive(Promptize)]
+pub struct Review {
+    system_prompt: String,
+    user_prompt: String,
+    #[chunkable(split = "lines", weight = 1)]
+    pub diff: String,
+    #[chunkable(split = "lines", weight = 3)]
+    pub file_content: String,
+    #[chunkable(priority = 1)]
+    pub ticket: String
+}
+
+fn field(prompt: &[tiktoken_rs::ChatCompletionRequestMessage], name: &str) -> String {
+    let user: serde_json::Value = serde_json::from_str(&prompt.last().unwrap().content).unwrap();
+    user[name].as_str().unwrap().to_string()
+}
+
+fn main() {
+    let bpe = tiktoken_rs::get_bpe_from_model("gpt-4").unwrap();
+    let token_limit = 1000;
+
+    let diff = (0..150)
+        .map(|i| format!("+ let value_{} = compute({});\n", i, i))
+        .collect::<String>();
+    let file_content = (0..400)
+        .map(|i| format!("line {} of the file says something about item {}\n", i, i * 7))
+        .collect::<String>();
+    let ticket = "The values computed in the loop are off by one.".to_string();
+
+    let prompts = Review::builder()
+        .system_prompt("You are a code reviewer.".to_string())
+        .user_prompt("Review this change.".to_string())
+        .diff(diff.clone())
+        .file_content(file_content.clone())
+        .ticket(ticket.clone())
+        .build_prompt("gpt-4", token_limit, 200)
+        .unwrap();
+
+    assert!(prompts.len() > 1);
+
+    for prompt in prompts.iter() {
+        let tokens: usize = prompt.messages
+            .iter()
+            .map(|m| bpe.encode_with_special_tokens(&m.content).len())
+            .sum();
+        assert!(tokens <= token_limit as usize);
+    }
+
+    // the ticket has the highest priority and fits whole, so it goes out with every request
+    assert!(prompts.iter().all(|prompt| field(&prompt.messages, "ticket") == ticket));
+
+    // both fields are cut into chunks that put them back together in order
+    let diffs = prompts.iter().map(|prompt| field(&prompt.messages, "diff")).collect::<Vec<_>>();
+    let files = prompts.iter().map(|prompt| field(&prompt.messages, "file_content")).collect::<Vec<_>>();
+    assert_eq!(diffs.concat(), diff);
+    assert_eq!(files.concat(), file_content);
+
+    // and they are cut at line ends
+    assert!(diffs.iter().chain(files.iter()).all(|chunk| chunk.is_empty() || chunk.ends_with('\n')));
+
+    // file_content has three times the weight of diff, so it gets the bigger share of each request
+    let diff_tokens = bpe.encode_with_special_tokens(&diffs[0]).len();
+    let file_tokens = bpe.encode_with_special_tokens(&files[0]).len();
+    assert!(file_tokens > diff_tokens * 2);
+
+    // an empty field has nothing to cut and doesn't stand in the way of the others
+    let prompts = Review::builder()
+        .system_prompt("You are a code reviewer.".to_string())
+        .user_prompt("Review this change.".to_string())
+        .diff(String::new())
+        .file_content(file_content.clone())
+        .ticket(ticket.clone())
+        .build_prompt("gpt-4", token_limit, 200)
+        .unwrap();
+
+    assert!(prompts.len() > 1);
+    assert!(prompts.iter().all(|prompt| field(&prompt.messages, "diff").is_empty()));
+
+    let files = prompts.iter().map(|prompt| field(&prompt.messages, "file_content")).collect::<Vec<_>>();
+    assert_eq!(files.concat(), file_content);
+}
@@ -0,0 +1,63 @@
+// #[promptize(layout = "cartesian")] sends every chunk of each chunkable field together with every
+// chunk of the others, for when each part of one field has to be looked at next to all of another.
+
+use promptize::Promptize;
+
+#[derive(Promptize)]
+#[promptize(layout = "cartesian")]
+pub struct Compare {
+    system_prompt: String,
+    #[chunkable(split = "lines")]
+    pub left: String,
+    #[chunkable(split = "lines")]
+    pub right: String
+}
+
+fn field(prompt: &[tiktoken_rs::ChatCompletionRequestMessage], name: &str) -> String {
+    let user: serde_json::Value = serde_json::from_str(&prompt.last().unwrap().content).unwrap();
+    user[name].as_str().unwrap().to_string()
+}
+
+fn main() {
+    let bpe = tiktoken_rs::get_bpe_from_model("gpt-4").unwrap();
+    let token_limit = 500;
+
+    let left = (0..60)
+        .map(|i| format!("left line {} mentions item {}\n", i, i * 3))
+        .collect::<String>();
+    let right = (0..60)
+        .map(|i| format!("right line {} mentions item {}\n", i, i * 5))
+        .collect::<String>();
+
+    let prompts = Compare::builder()
+        .system_prompt("Find the items both sides mention.".to_string())
+        .left(left.clone())
+        .right(right.clone())
+        .build_prompt("gpt-4", token_limit, 100)
+        .unwrap();
+
+    for prompt in prompts.iter() {
+        let tokens: usize = prompt.messages
+            .iter()
+            .map(|m| bpe.encode_with_special_tokens(&m.content).len())
+            .sum();
+        assert!(tokens <= token_limit as usize);
+    }
+
+    let mut lefts = prompts.iter().map(|prompt| field(&prompt.messages, "left")).collect::<Vec<_>>();
+    let mut rights = prompts.iter().map(|prompt| field(&prompt.messages, "right")).collect::<Vec<_>>();
+    lefts.dedup();
+    rights.sort();
+    rights.dedup();
+
+    // every left chunk is paired with every right chunk
+    assert!(lefts.len() > 1 && rights.len() > 1);
+    assert_eq!(prompts.len(), lefts.len() * rights.len());
+    assert_eq!(lefts.concat(), left);
+
+    let pairs = prompts
+        .iter()
+        .map(|prompt| (field(&prompt.messages, "left"), field(&prompt.messages, "right")))
+        .collect::<std::collections::HashSet<_>>();
+    assert_eq!(pairs.len(), prompts.len());
+}
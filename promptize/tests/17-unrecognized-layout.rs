@@ -0,0 +1,15 @@
+// The layout has to be one of the known ways of combining chunks.
+
+use promptize::Promptize;
+
+#[derive(Promptize)]
+#[promptize(layout = "interleave")]
+pub struct Compare {
+    system_prompt: String,
+    #[chunkable]
+    pub left: String,
+    #[chunkable]
+    pub right: String
+}
+
+fn main() {}
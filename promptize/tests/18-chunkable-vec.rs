@@ -0,0 +1,62 @@
+// A Vec can be #[chunkable] as long as its elements can be serialized. Collections are never cut in
+// the middle of an element, each chunk gets the whole elements that fit in the token budget.
+
+use promptize::Promptize;
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, PartialEq)]
+pub struct LogLine {
+    level: String,
+    message: String
+}
+
+#[derive(Promptize)]
+pub struct LogAnalysis {
+    system_prompt: String,
+    user_prompt: String,
+    pub service: String,
+    #[chunkable]
+    pub records: Vec<LogLine>
+}
+
+fn count_tokens(bpe: &tiktoken_rs::CoreBPE, prompt: &[tiktoken_rs::ChatCompletionRequestMessage]) -> usize {
+    prompt
+        .iter()
+        .map(|m| bpe.encode_with_special_tokens(&m.content).len())
+        .sum()
+}
+
+fn main() {
+    let bpe = tiktoken_rs::get_bpe_from_model("gpt-4").unwrap();
+    let token_limit = 1000;
+
+    let records = (0..200)
+        .map(|i| LogLine {
+            level: if i % 10 == 0 { "error" } else { "info" }.to_string(),
+            message: format!("request {} finished after {}ms", i, i * 13)
+        })
+        .collect::<Vec<_>>();
+
+    let prompts = LogAnalysis::builder()
+        .system_prompt("You are a log analyzer that responds only in JSON.".to_string())
+        .user_prompt("Find the requests that failed.".to_string())
+        .service("checkout".to_string())
+        .records(records.clone())
+        .build_prompt("gpt-4", token_limit, 200)
+        .unwrap();
+
+    assert!(prompts.len() > 1);
+
+    // every chunk is a list of whole records and together they are the records in order
+    let mut chunked = vec![];
+    for prompt in prompts.iter() {
+        assert!(count_tokens(&bpe, &prompt.messages) <= token_limit as usize);
+
+        let user: serde_json::Value = serde_json::from_str(&prompt.messages.last().unwrap().content).unwrap();
+        assert_eq!(user["service"], "checkout");
+
+        let chunk: Vec<LogLine> = serde_json::from_value(user["records"].clone()).unwrap();
+        assert!(!chunk.is_empty());
+        chunked.extend(chunk);
+    }
+    assert_eq!(chunked, records);
+}
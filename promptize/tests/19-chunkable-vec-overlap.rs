@@ -0,0 +1,57 @@
+// #[chunkable(overlap = N)] on a collection repeats the elements at the end of each chunk that fit
+// in N tokens at the start of the next one.
+
+use promptize::Promptize;
+
+#[derive(Promptize)]
+pub struct Notes {
+    system_prompt: String,
+    #[chunkable(overlap = 30)]
+    pub notes: Vec<String>
+}
+
+fn count_tokens(bpe: &tiktoken_rs::CoreBPE, prompt: &[tiktoken_rs::ChatCompletionRequestMessage]) -> usize {
+    prompt
+        .iter()
+        .map(|m| bpe.encode_with_special_tokens(&m.content).len())
+        .sum()
+}
+
+fn main() {
+    let bpe = tiktoken_rs::get_bpe_from_model("gpt-4").unwrap();
+
+    let notes = (0..150)
+        .map(|i| format!("note {} says to look at item {}", i, i * 3))
+        .collect::<Vec<_>>();
+
+    let prompts = Notes::builder()
+        .system_prompt("Summarize these notes.".to_string())
+        .notes(notes.clone())
+        .build_prompt("gpt-4", 500, 100)
+        .unwrap();
+
+    let chunks = prompts
+        .iter()
+        .map(|prompt| {
+            assert!(count_tokens(&bpe, &prompt.messages) <= 500);
+
+            let user: serde_json::Value = serde_json::from_str(&prompt.messages.last().unwrap().content).unwrap();
+            serde_json::from_value::<Vec<String>>(user["notes"].clone()).unwrap()
+        })
+        .collect::<Vec<_>>();
+
+    assert!(chunks.len() > 1);
+
+    let mut joined = chunks[0].clone();
+    for pair in chunks.windows(2) {
+        let (prev, next) = (&pair[0], &pair[1]);
+        let repeated = (1..=next.len()).filter(|&n| prev.ends_with(&next[..n])).max().unwrap();
+        let repeated_tokens: usize = next[..repeated]
+            .iter()
+            .map(|note| bpe.encode_with_special_tokens(&serde_json::to_string(note).unwrap()).len())
+            .sum();
+        assert!(repeated_tokens <= 30);
+        joined.extend_from_slice(&next[repeated..]);
+    }
+    assert_eq!(joined, notes);
+}
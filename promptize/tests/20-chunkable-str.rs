@@ -0,0 +1,54 @@
+// Borrowed text can be #[chunkable] too, as a &str or a Cow<str>. The builder borrows what the
+// struct does, so the text does not have to be copied into it.
+
+use promptize::Promptize;
+use std::borrow::Cow;
+
+#[derive(Promptize)]
+pub struct Summary<'a> {
+    system_prompt: String,
+    pub title: String,
+    #[chunkable(split = "lines")]
+    pub body: &'a str,
+    #[chunkable(split = "lines")]
+    pub appendix: Cow<'a, str>
+}
+
+fn main() {
+    let bpe = tiktoken_rs::get_bpe_from_model("gpt-4").unwrap();
+    let token_limit = 600;
+
+    let body = (0..150)
+        .map(|i| format!("paragraph {} of the report covers region {}\n", i, i % 12))
+        .collect::<String>();
+    let appendix = (0..80)
+        .map(|i| format!("table row {}: {} units\n", i, i * 40))
+        .collect::<String>();
+
+    let prompts = Summary::builder()
+        .system_prompt("Summarize the report.".to_string())
+        .title("Quarterly report".to_string())
+        .body(&body)
+        .appendix(Cow::Borrowed(&appendix))
+        .build_prompt("gpt-4", token_limit, 200)
+        .unwrap();
+
+    assert!(prompts.len() > 1);
+
+    let mut bodies = String::new();
+    let mut appendices = String::new();
+    for prompt in prompts.iter() {
+        let tokens: usize = prompt.messages
+            .iter()
+            .map(|m| bpe.encode_with_special_tokens(&m.content).len())
+            .sum();
+        assert!(tokens <= token_limit as usize);
+
+        let user: serde_json::Value = serde_json::from_str(&prompt.messages.last().unwrap().content).unwrap();
+        bodies.push_str(user["body"].as_str().unwrap());
+        appendices.push_str(user["appendix"].as_str().unwrap());
+    }
+
+    assert_eq!(bodies, body);
+    assert_eq!(appendices, appendix);
+}
@@ -0,0 +1,12 @@
+// Only text and collections can be cut up into chunks.
+
+use promptize::Promptize;
+
+#[derive(Promptize)]
+pub struct Totals {
+    system_prompt: String,
+    #[chunkable]
+    pub total: u64
+}
+
+fn main() {}
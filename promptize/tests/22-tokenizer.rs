@@ -0,0 +1,67 @@
+// build_prompt_with counts and cuts tokens with any Tokenizer, for models tiktoken doesn't know.
+// promptize comes with tiktoken's tokenizers, HuggingFace's behind the "huggingface" feature, and a
+// heuristic that needs no vocabulary at all. Anything else can implement the trait.
+
+use promptize::{HeuristicTokenizer, Promptize, PromptizeError, Tokenizer};
+
+#[derive(Promptize)]
+pub struct FileContent {
+    system_prompt: String,
+    pub filename: String,
+    #[chunkable(split = "lines")]
+    pub file_content: String
+}
+
+/// A token for every char, ids are the chars themselves
+struct CharTokenizer;
+
+impl Tokenizer for CharTokenizer {
+    fn encode(&self, text: &str) -> Result<Vec<usize>, PromptizeError> {
+        Ok(text.chars().map(|c| c as usize).collect())
+    }
+
+    fn decode(&self, tokens: &[usize]) -> Option<String> {
+        tokens.iter().map(|&id| char::from_u32(id as u32)).collect()
+    }
+}
+
+fn chunks<T: Tokenizer>(tokenizer: &T, content: &str, token_limit: i32) -> Vec<String> {
+    let prompts = FileContent::builder()
+        .system_prompt("Summarize this file.".to_string())
+        .filename("notes.txt".to_string())
+        .file_content(content.to_string())
+        .build_prompt_with(tokenizer, token_limit, 100)
+        .unwrap();
+
+    prompts
+        .iter()
+        .map(|prompt| {
+            let tokens: usize = prompt.messages.iter().map(|m| tokenizer.count(&m.content).unwrap()).sum();
+            assert!(tokens <= token_limit as usize);
+
+            let user: serde_json::Value = serde_json::from_str(&prompt.messages.last().unwrap().content).unwrap();
+            user["file_content"].as_str().unwrap().to_string()
+        })
+        .collect()
+}
+
+fn main() {
+    let content = (0..100)
+        .map(|i| format!("entry {} in the notes, ünïcödé included — {}\n", i, i * 11))
+        .collect::<String>();
+
+    // the heuristic decodes back to the exact text, so it can be used to cut chunks
+    let heuristic = HeuristicTokenizer::default();
+    let text = "fn main() { println!(\"héllo wörld\"); }";
+    let tokens = heuristic.encode(text).unwrap();
+    assert_eq!(heuristic.decode(&tokens).unwrap(), text);
+    assert!(tokens.len() > text.len() / 8 && tokens.len() < text.len());
+
+    let heuristic_chunks = chunks(&heuristic, &content, 500);
+    assert!(heuristic_chunks.len() > 1);
+    assert_eq!(heuristic_chunks.concat(), content);
+
+    let char_chunks = chunks(&CharTokenizer, &content, 1000);
+    assert!(char_chunks.len() > heuristic_chunks.len());
+    assert_eq!(char_chunks.concat(), content);
+}
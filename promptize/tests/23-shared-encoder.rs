@@ -0,0 +1,49 @@
+// Encoders are built the first time a model asks for one and shared from then on, so prompts can be
+// built from many threads at once without each of them building its own.
+
+use promptize::{Promptize, TiktokenTokenizer, Tokenizer};
+
+#[derive(Promptize)]
+pub struct FileContent {
+    system_prompt: String,
+    pub filename: String,
+    #[chunkable(split = "lines")]
+    pub file_content: String
+}
+
+fn assert_send_sync<T: Send + Sync>() {}
+
+fn main() {
+    assert_send_sync::<TiktokenTokenizer>();
+
+    let handles = (0..8)
+        .map(|i| {
+            std::thread::spawn(move || {
+                let content = (0..300)
+                    .map(|line| format!("line {} of file {}\n", line, i))
+                    .collect::<String>();
+
+                // gpt-4 and gpt-3.5-turbo use the same encoding and get the same encoder
+                let model = if i % 2 == 0 { "gpt-4" } else { "gpt-3.5-turbo" };
+
+                FileContent::builder()
+                    .system_prompt("Summarize this file.".to_string())
+                    .filename(format!("file_{}.txt", i))
+                    .file_content(content)
+                    .build_prompt(model, 1000, 200)
+                    .unwrap()
+            })
+        })
+        .collect::<Vec<_>>();
+
+    let bpe = TiktokenTokenizer::for_model("gpt-4").unwrap();
+    for handle in handles {
+        let prompts = handle.join().unwrap();
+        assert!(prompts.len() > 1);
+
+        for prompt in prompts {
+            let tokens: usize = prompt.messages.iter().map(|m| bpe.count(&m.content).unwrap()).sum();
+            assert!(tokens <= 1000);
+        }
+    }
+}
@@ -0,0 +1,67 @@
+// build_prompt_for works out the token limits from the model registry: the model's context window,
+// less the tokens reserved for the reply and what the chat format adds around every message.
+// OpenAI's chat models are built in and others can be registered with register_model.
+
+use promptize::{model_info, register_model, HeuristicTokenizer, ModelInfo, Promptize, PromptizeError};
+
+#[derive(Promptize)]
+pub struct FileContent {
+    system_prompt: String,
+    pub filename: String,
+    #[chunkable(split = "lines")]
+    pub file_content: String
+}
+
+fn builder(content: &str) -> FileContentBuilder {
+    let mut builder = FileContent::builder();
+    builder
+        .system_prompt("Summarize this file.".to_string())
+        .filename("notes.txt".to_string())
+        .file_content(content.to_string());
+    builder
+}
+
+fn main() {
+    let content = (0..2500)
+        .map(|i| format!("line {} of the file says something about item {}\n", i, i * 7))
+        .collect::<String>();
+
+    // dated snapshots get the context window of their model
+    let gpt4 = model_info("gpt-4-0613").unwrap();
+    assert_eq!(gpt4.context_window, 8192);
+    assert_eq!(model_info("gpt-4-32k").unwrap().context_window, 32768);
+
+    let prompts = builder(&content).build_prompt_for("gpt-4", 1000).unwrap();
+    assert!(prompts.len() > 1);
+
+    for prompt in prompts.iter() {
+        let tokens: i32 = prompt.messages
+            .iter()
+            .map(|m| {
+                let text = gpt4.tokenizer().count(&m.content).unwrap() + gpt4.tokenizer().count(&m.role).unwrap();
+                text as i32 + gpt4.tokens_per_message
+            })
+            .sum();
+        assert!(tokens + gpt4.tokens_per_reply <= 8192 - 1000);
+    }
+
+    // a bigger context window needs fewer requests
+    let prompts_32k = builder(&content).build_prompt_for("gpt-4-32k", 1000).unwrap();
+    assert!(prompts_32k.len() < prompts.len());
+
+    // models that aren't built in have to be registered
+    match builder(&content).build_prompt_for("local-llama", 500) {
+        Err(PromptizeError::UnknownModel(model)) => assert_eq!(model, "local-llama"),
+        _ => panic!("expected UnknownModel")
+    }
+
+    register_model("local-llama", ModelInfo::new(2048, HeuristicTokenizer::default()).message_overhead(5, 0));
+    let prompts = builder(&content).build_prompt_for("local-llama", 500).unwrap();
+    assert!(prompts.len() > 1);
+
+    // reserving the whole context window leaves no room for a prompt
+    match builder(&content).build_prompt_for("gpt-4", 8192) {
+        Err(PromptizeError::BudgetTooSmall { required, .. }) => assert_eq!(required, 8192),
+        _ => panic!("expected BudgetTooSmall")
+    }
+}
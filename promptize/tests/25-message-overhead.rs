@@ -0,0 +1,51 @@
+// Chat models wrap every message in tokens of their own and prime the reply with a few more, so a
+// prompt is counted the way tiktoken's num_tokens_from_messages counts it, and every prompt that
+// comes back has been checked against the limit that way.
+
+use promptize::{Promptize, TiktokenTokenizer, Tokenizer};
+
+#[derive(Promptize)]
+pub struct FileContent {
+    system_prompt: String,
+    user_prompt: String,
+    pub filename: String,
+    #[chunkable(split = "lines")]
+    pub file_content: String
+}
+
+fn main() {
+    let content = (0..400)
+        .map(|i| format!("line {} of the file says something about item {}\n", i, i * 7))
+        .collect::<String>();
+
+    let mut builder = FileContent::builder();
+    builder
+        .system_prompt("You are a computer system that responds only in JSON.".to_string())
+        .user_prompt("Summarize this file.".to_string())
+        .filename("huge_file.txt".to_string())
+        .file_content(content.clone());
+
+    let prompts = builder.build_prompt("gpt-4", 1000, 200).unwrap();
+    assert!(prompts.len() > 1);
+
+    for prompt in prompts.iter() {
+        assert!(tiktoken_rs::num_tokens_from_messages("gpt-4", &prompt.messages).unwrap() <= 1000);
+    }
+
+    // a prompt whose content alone fits in the limit doesn't fit once its three messages are
+    // wrapped, so it is cut up rather than sent whole
+    let single = builder.build_prompt("gpt-4", 100_000, 200).unwrap();
+    assert_eq!(single.len(), 1);
+
+    let bpe = TiktokenTokenizer::for_model("gpt-4").unwrap();
+    let content_tokens: usize = single[0].messages.iter().map(|m| bpe.count(&m.content).unwrap()).sum();
+    let message_tokens = tiktoken_rs::num_tokens_from_messages("gpt-4", &single[0].messages).unwrap();
+    assert_eq!(bpe.count_messages(&single[0].messages).unwrap(), message_tokens);
+    assert!(message_tokens > content_tokens);
+
+    let prompts = builder.build_prompt("gpt-4", content_tokens as i32, 200).unwrap();
+    assert!(prompts.len() > 1);
+    for prompt in prompts.iter() {
+        assert!(tiktoken_rs::num_tokens_from_messages("gpt-4", &prompt.messages).unwrap() <= content_tokens);
+    }
+}
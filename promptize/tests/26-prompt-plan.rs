@@ -0,0 +1,86 @@
+// build_prompt returns a PromptPlan. Along with its messages, each chunk knows its token count, where
+// it is in the plan and the range of every chunkable field it covers: bytes, chars and lines for
+// text and elements for collections. The ranges map answers back to the source they are about.
+
+use promptize::{ChunkRange, Promptize, TiktokenTokenizer, Tokenizer};
+
+#[derive(Promptize)]
+pub struct FileContent {
+    system_prompt: String,
+    pub filename: String,
+    #[chunkable(split = "lines")]
+    pub file_content: String,
+    #[chunkable]
+    pub notes: Vec<String>
+}
+
+fn main() {
+    let bpe = TiktokenTokenizer::for_model("gpt-4").unwrap();
+
+    let content = (0..300)
+        .map(|i| format!("lïne {} of the file says something about item {}\n", i, i * 7))
+        .collect::<String>();
+    let notes = (0..60).map(|i| format!("note {}", i)).collect::<Vec<_>>();
+
+    let plan = FileContent::builder()
+        .system_prompt("Summarize this file.".to_string())
+        .filename("huge_file.txt".to_string())
+        .file_content(content.clone())
+        .notes(notes.clone())
+        .build_prompt("gpt-4", 1000, 200)
+        .unwrap();
+
+    assert!(plan.len() > 1);
+
+    let mut next_line = 0;
+    for (i, chunk) in plan.iter().enumerate() {
+        assert_eq!(chunk.index, i);
+        assert_eq!(chunk.total, plan.len());
+        assert_eq!(chunk.tokens, bpe.count_messages(&chunk.messages).unwrap());
+        assert!(chunk.tokens <= 1000);
+
+        let user: serde_json::Value = serde_json::from_str(&chunk.messages.last().unwrap().content).unwrap();
+
+        assert_eq!(chunk.sources[0].field, "file_content");
+        match &chunk.sources[0].range {
+            ChunkRange::Text { bytes, chars, lines } => {
+                let text = user["file_content"].as_str().unwrap();
+                assert_eq!(&content[bytes.clone()], text);
+
+                let by_chars = content.chars().skip(chars.start).take(chars.len()).collect::<String>();
+                assert_eq!(by_chars, text);
+
+                // chunks are cut at line ends, so each one starts on the line after the last
+                let by_lines = content.lines().skip(lines.start).take(lines.len()).collect::<Vec<_>>();
+                assert_eq!(by_lines, text.lines().collect::<Vec<_>>());
+                assert_eq!(lines.start, next_line);
+                next_line = lines.end;
+            },
+            range => panic!("expected a text range, got {:?}", range)
+        }
+
+        assert_eq!(chunk.sources[1].field, "notes");
+        match &chunk.sources[1].range {
+            ChunkRange::Elements(range) => {
+                let chunk_notes: Vec<String> = serde_json::from_value(user["notes"].clone()).unwrap();
+                assert_eq!(&notes[range.clone()], &chunk_notes[..]);
+            },
+            range => panic!("expected an element range, got {:?}", range)
+        }
+    }
+    assert_eq!(next_line, 300);
+
+    // a prompt that fits in one request covers the whole of every field
+    let plan = FileContent::builder()
+        .system_prompt("Summarize this file.".to_string())
+        .filename("small_file.txt".to_string())
+        .file_content("one line\n".to_string())
+        .notes(vec!["a note".to_string()])
+        .build_prompt("gpt-4", 1000, 200)
+        .unwrap();
+
+    assert_eq!(plan.len(), 1);
+    assert_eq!(plan[0].sources[0].range, ChunkRange::Text { bytes: 0..9, chars: 0..9, lines: 0..1 });
+    assert_eq!(plan[0].sources[1].range, ChunkRange::Elements(0..1));
+    assert_eq!(plan.into_messages()[0].len(), 2);
+}
@@ -0,0 +1,107 @@
+// The answers to the requests of a chunked prompt are combined with build_reduce_prompt. It puts
+// as many of them as fit in a request after the role messages and the struct's reduce_prompt,
+// and splits them over more requests when they don't all fit. reduce keeps combining the answers
+// it gets back until there is only one.
+
+use promptize::{ChunkRange, Promptize, PromptizeError, TiktokenTokenizer, Tokenizer};
+
+#[derive(Promptize)]
+#[promptize(reduce_prompt = "Merge these summaries of one file into a single summary.")]
+pub struct FileContent {
+    system_prompt: String,
+    user_prompt: String,
+    pub filename: String,
+    #[chunkable(split = "lines")]
+    pub file_content: String
+}
+
+fn main() {
+    let bpe = TiktokenTokenizer::for_model("gpt-4").unwrap();
+
+    let content = (0..400)
+        .map(|i| format!("line {} of the file says something about item {}\n", i, i * 7))
+        .collect::<String>();
+
+    let mut builder = FileContent::builder();
+    builder
+        .system_prompt("You are a computer system that summarizes files.".to_string())
+        .user_prompt("Summarize this file.".to_string())
+        .filename("huge_file.txt".to_string())
+        .file_content(content);
+
+    let plan = builder.build_prompt("gpt-4", 1000, 200).unwrap();
+    assert!(plan.len() > 1);
+
+    let answers = plan
+        .iter()
+        .map(|chunk| format!("Part {} is about {} items, each said something. ", chunk.index, chunk.index * 50).repeat(10))
+        .collect::<Vec<String>>();
+
+    // everything fits in one request
+    let reduce = builder.build_reduce_prompt(&answers, "gpt-4", 8000).unwrap();
+    assert_eq!(reduce.len(), 1);
+    assert_eq!(reduce[0].sources[0].field, "partial_answers");
+    assert_eq!(reduce[0].sources[0].range, ChunkRange::Elements(0..answers.len()));
+
+    let messages = &reduce[0].messages;
+    assert_eq!(messages.len(), 3);
+    assert_eq!(messages[0].content, "You are a computer system that summarizes files.");
+    assert_eq!(messages[1].content, "Summarize this file.");
+    assert!(messages[2].content.starts_with("Merge these summaries of one file into a single summary."));
+    for answer in &answers {
+        assert!(messages[2].content.contains(answer.as_str()));
+    }
+    // the file itself is not sent again
+    assert!(!messages[2].content.contains("huge_file.txt"));
+
+    // the answers are split over as many requests as it takes, in order
+    let reduce = builder.build_reduce_prompt(&answers, "gpt-4", 400).unwrap();
+    assert!(reduce.len() > 1 && reduce.len() < answers.len());
+
+    let mut next = 0;
+    for chunk in &reduce {
+        assert!(chunk.tokens <= 400);
+        assert_eq!(chunk.tokens, bpe.count_messages(&chunk.messages).unwrap());
+
+        match &chunk.sources[0].range {
+            ChunkRange::Elements(range) => {
+                assert_eq!(range.start, next);
+                next = range.end;
+
+                let content = &chunk.messages.last().unwrap().content;
+                for i in range.clone() {
+                    assert!(content.contains(&format!("Part {} of {}:\n{}", i + 1, answers.len(), answers[i])));
+                }
+            },
+            range => panic!("expected an element range, got {:?}", range)
+        }
+    }
+    assert_eq!(next, answers.len());
+
+    // requests that can't fit two answers would never get down to one
+    let error = builder.build_reduce_prompt(&answers, "gpt-4", 200).unwrap_err();
+    assert!(matches!(error, PromptizeError::BudgetTooSmall { .. }));
+
+    // reduce combines answers until there is one left
+    let mut rounds = vec![];
+    let answer = builder
+        .reduce(answers.clone(), "gpt-4", 400, |chunk| -> Result<String, PromptizeError> {
+            if chunk.index == 0 {
+                rounds.push(chunk.total);
+            }
+            Ok(format!("combined {} of {}", chunk.index + 1, chunk.total))
+        })
+        .unwrap();
+
+    assert_eq!(answer, "combined 1 of 1");
+    assert_eq!(rounds.first(), Some(&reduce.len()));
+    assert_eq!(rounds.last(), Some(&1));
+
+    // a single answer is already combined
+    let answer = builder
+        .reduce(vec!["just one".to_string()], "gpt-4", 400, |_| -> Result<String, PromptizeError> {
+            panic!("nothing to combine")
+        })
+        .unwrap();
+    assert_eq!(answer, "just one");
+}
@@ -0,0 +1,62 @@
+// #[promptize(annotate_chunks)] starts the user message of every chunk with a header that tells the
+// model which part of the input it is looking at. The header is a template with {part}, {parts},
+// {first} and {last} placeholders next to the fields of the struct, and its tokens come out of the
+// budget of the chunks. A prompt that fits in one request goes out without one.
+
+use promptize::{Promptize, TiktokenTokenizer, Tokenizer};
+
+#[derive(Promptize)]
+#[promptize(annotate_chunks = "Part {part} of {parts}, lines {first}-{last} of {filename}")]
+pub struct FileContent {
+    system_prompt: String,
+    pub filename: String,
+    #[chunkable(split = "lines")]
+    pub file_content: String
+}
+
+fn main() {
+    let bpe = TiktokenTokenizer::for_model("gpt-4").unwrap();
+
+    let content = (1..=500)
+        .map(|i| format!("line {} of the file says something about item {}\n", i, i * 7))
+        .collect::<String>();
+
+    let mut builder = FileContent::builder();
+    builder
+        .system_prompt("You are a code reviewer.".to_string())
+        .filename("huge_file.rs".to_string())
+        .file_content(content.clone());
+
+    let plan = builder.build_prompt("gpt-4", 600, 200).unwrap();
+    assert!(plan.len() > 1);
+
+    let mut next_line = 1;
+    for chunk in &plan {
+        assert!(chunk.tokens <= 600);
+        assert_eq!(chunk.tokens, bpe.count_messages(&chunk.messages).unwrap());
+
+        let (header, json) = chunk.messages[1].content.split_once("\n\n").unwrap();
+        let user: serde_json::Value = serde_json::from_str(json).unwrap();
+        let text = user["file_content"].as_str().unwrap();
+
+        // every line of the file says which line it is
+        let first = text.lines().next().unwrap().split(' ').nth(1).unwrap();
+        let last = text.lines().last().unwrap().split(' ').nth(1).unwrap();
+        assert_eq!(
+            header,
+            format!("Part {} of {}, lines {}-{} of huge_file.rs", chunk.index + 1, plan.len(), first, last)
+        );
+
+        assert_eq!(first.parse::<usize>().unwrap(), next_line);
+        next_line = last.parse::<usize>().unwrap() + 1;
+    }
+    assert_eq!(next_line, 501);
+
+    let plan = builder
+        .file_content("fn main() {}\n".to_string())
+        .build_prompt("gpt-4", 600, 200)
+        .unwrap();
+
+    assert_eq!(plan.len(), 1);
+    assert!(plan[0].messages[1].content.starts_with('{'));
+}
@@ -0,0 +1,56 @@
+// The chunking the derive needs lives in the promptize crate instead of being generated next to
+// every struct, so any number of structs in one module can derive Promptize.
+
+use promptize::Promptize;
+
+#[derive(Promptize)]
+pub struct FileContent {
+    system_prompt: String,
+    pub filename: String,
+    #[chunkable(split = "lines")]
+    pub file_content: String
+}
+
+#[derive(Promptize)]
+#[promptize(template = "Review {filename}:\n{diff}", annotate_chunks)]
+pub struct Diff {
+    system_prompt: String,
+    pub filename: String,
+    #[chunkable(split = "lines")]
+    pub diff: String
+}
+
+#[derive(Promptize)]
+pub struct Tickets {
+    system_prompt: String,
+    #[chunkable]
+    pub tickets: Vec<String>
+}
+
+fn main() {
+    let content = "fn main() {}\n".repeat(2000);
+
+    let files = FileContent::builder()
+        .system_prompt("You are a code reviewer.".to_string())
+        .filename("main.rs".to_string())
+        .file_content(content.clone())
+        .build_prompt("gpt-4", 1000, 200)
+        .unwrap();
+    assert!(files.len() > 1);
+
+    let diffs = Diff::builder()
+        .system_prompt("You are a code reviewer.".to_string())
+        .filename("main.rs".to_string())
+        .diff(content)
+        .build_prompt("gpt-4", 1000, 200)
+        .unwrap();
+    assert!(diffs.len() > 1);
+    assert!(diffs[0].messages[1].content.starts_with(&format!("Part 1 of {}, lines 1-", diffs.len())));
+
+    let tickets = Tickets::builder()
+        .system_prompt("Group these tickets.".to_string())
+        .tickets((0..500).map(|i| format!("ticket {}: the build is broken again", i)).collect())
+        .build_prompt("gpt-4", 1000, 200)
+        .unwrap();
+    assert!(tickets.len() > 1);
+}
@@ -0,0 +1,54 @@
+// estimate works out what build_prompt_for would send without keeping the messages: the number of
+// requests, the tokens of each and what the prompt tokens cost at the model's input_price. Prices
+// can be set by registering a model.
+
+use promptize::{model_info, register_model, Promptize};
+
+#[derive(Promptize)]
+pub struct FileContent {
+    system_prompt: String,
+    pub filename: String,
+    #[chunkable(split = "lines")]
+    pub file_content: String
+}
+
+fn main() {
+    let content = (0..3000)
+        .map(|i| format!("line {} of the file says something about item {}\n", i, i * 7))
+        .collect::<String>();
+
+    let mut builder = FileContent::builder();
+    builder
+        .system_prompt("You are a code reviewer.".to_string())
+        .filename("huge_file.rs".to_string())
+        .file_content(content);
+
+    let estimate = builder.estimate("gpt-4", 1024).unwrap();
+    let plan = builder.build_prompt_for("gpt-4", 1024).unwrap();
+
+    assert_eq!(estimate.model, "gpt-4");
+    assert!(estimate.requests() > 1);
+    assert_eq!(estimate.requests(), plan.len());
+    assert_eq!(estimate.tokens_per_request, plan.iter().map(|chunk| chunk.tokens).collect::<Vec<_>>());
+    assert_eq!(estimate.total_tokens, estimate.tokens_per_request.iter().sum::<usize>());
+
+    // gpt-4 is $30 for a million prompt tokens
+    let price = estimate.price.unwrap();
+    assert!((price - estimate.total_tokens as f64 * 30.0 / 1_000_000.0).abs() < 1e-9);
+
+    let report = estimate.to_string();
+    assert!(report.starts_with(&format!("gpt-4: {} requests, {} prompt tokens, $", estimate.requests(), estimate.total_tokens)));
+    assert!(report.contains(&format!("  request 1: {} tokens", estimate.tokens_per_request[0])));
+
+    // a registered price wins over the built in one, and a model without one has no price
+    register_model("gpt-4-discounted", model_info("gpt-4").unwrap().input_price(15.0));
+    let discounted = builder.estimate("gpt-4-discounted", 1024).unwrap();
+    assert!((discounted.price.unwrap() - price / 2.0).abs() < 1e-9);
+
+    let mut unpriced = model_info("gpt-4").unwrap();
+    unpriced.input_price = None;
+    register_model("gpt-4-unpriced", unpriced);
+    let unpriced = builder.estimate("gpt-4-unpriced", 1024).unwrap();
+    assert_eq!(unpriced.price, None);
+    assert!(unpriced.to_string().contains("price unknown"));
+}
@@ -0,0 +1,86 @@
+// #[promptize(response = T)] asks the model to answer with JSON of type T. The JSON schema of T
+// goes at the end of the system message, or in a system message of its own when there isn't one,
+// and counts against the token limit like any other part of the prompt. parse_response reads an
+// answer back into a T, from inside of a markdown code block if the model put it in one.
+
+use promptize::{Promptize, PromptizeError, TiktokenTokenizer, Tokenizer};
+
+#[derive(serde::Deserialize, schemars::JsonSchema, Debug, PartialEq)]
+pub struct Summary {
+    pub summary: String,
+    #[serde(rename = "todoCount")]
+    pub todo_count: u32,
+    pub functions: Vec<String>
+}
+
+#[derive(Promptize)]
+#[promptize(response = Summary)]
+pub struct FileContent {
+    system_prompt: String,
+    pub filename: String,
+    #[chunkable(split = "lines")]
+    pub file_content: String
+}
+
+#[derive(Promptize)]
+#[promptize(response = Summary)]
+pub struct Notes {
+    #[chunkable]
+    pub notes: String
+}
+
+fn main() {
+    let bpe = TiktokenTokenizer::for_model("gpt-4").unwrap();
+
+    let mut builder = FileContent::builder();
+    builder
+        .system_prompt("You summarize rust files.".to_string())
+        .filename("main.rs".to_string())
+        .file_content("fn main() {}\n".to_string());
+
+    let plan = builder.build_prompt("gpt-4", 1000, 200).unwrap();
+    let system = &plan[0].messages[0];
+    assert_eq!(system.role, "system");
+
+    let (prompt, instructions) = system.content.split_once("\n\n").unwrap();
+    assert_eq!(prompt, "You summarize rust files.");
+    let (_, schema) = instructions.split_once('\n').unwrap();
+    let schema: serde_json::Value = serde_json::from_str(schema).unwrap();
+    assert_eq!(schema["type"], "object");
+    assert!(schema["properties"]["todoCount"].is_object());
+    assert_eq!(schema["properties"]["functions"]["type"], "array");
+
+    // the schema is sent with every chunk and the chunks still fit
+    let plan = builder
+        .file_content("fn main() {}\n".repeat(2000))
+        .build_prompt("gpt-4", 1000, 200)
+        .unwrap();
+    assert!(plan.len() > 1);
+    for chunk in &plan {
+        assert_eq!(chunk.messages[0].content, system.content);
+        assert!(chunk.tokens <= 1000);
+        assert_eq!(chunk.tokens, bpe.count_messages(&chunk.messages).unwrap());
+    }
+
+    // without a system prompt the schema gets a system message of its own
+    let plan = Notes::builder().notes("a note".to_string()).build_prompt("gpt-4", 1000, 200).unwrap();
+    assert_eq!(plan[0].messages.len(), 2);
+    assert_eq!(plan[0].messages[0].role, "system");
+    assert!(plan[0].messages[0].content.starts_with("Respond only with JSON"));
+
+    let expected = Summary {
+        summary: "an empty program".to_string(),
+        todo_count: 0,
+        functions: vec!["main".to_string()]
+    };
+    let json = r#"{"summary": "an empty program", "todoCount": 0, "functions": ["main"]}"#;
+
+    assert_eq!(builder.parse_response(json).unwrap(), expected);
+    assert_eq!(builder.parse_response(&format!("```json\n{}\n```", json)).unwrap(), expected);
+    assert_eq!(builder.parse_response(&format!("Here you go:\n```\n{}\n```\nAnything else?", json)).unwrap(), expected);
+    assert_eq!(builder.parse_response(&format!("```json {} ```", json)).unwrap(), expected);
+    assert_eq!(builder.parse_response(&format!("Here you go: ```{}```", json)).unwrap(), expected);
+
+    let error = builder.parse_response("I can't summarize this file.").unwrap_err();
+    assert!(matches!(error, PromptizeError::InvalidResponse(_)));
+}
@@ -0,0 +1,129 @@
+// A #[history] field holds the turns of a conversation so far, which go in between the system
+// messages and the rest. When a prompt is over the limit the history is budgeted along with the
+// chunkable fields, below them unless its priority says otherwise, and only the most recent turns
+// that fit in its share are sent. A summarize hook can stand in for the turns that are dropped.
+
+use promptize::{ChatCompletionRequestMessage, Promptize, Role, TiktokenTokenizer, Tokenizer};
+
+#[derive(Promptize)]
+pub struct Chat {
+    system_prompt: String,
+    #[history]
+    pub history: Vec<ChatCompletionRequestMessage>,
+    #[chunkable(split = "lines")]
+    pub document: String
+}
+
+#[derive(Promptize)]
+pub struct PinnedChat {
+    system_prompt: String,
+    #[history(priority = 1)]
+    pub history: Vec<ChatCompletionRequestMessage>,
+    #[chunkable(split = "lines")]
+    pub document: String
+}
+
+#[derive(Promptize)]
+pub struct SummarizedChat {
+    system_prompt: String,
+    #[history(summarize = summarize)]
+    pub history: Vec<ChatCompletionRequestMessage>,
+    #[chunkable(split = "lines")]
+    pub document: String
+}
+
+fn summarize(turns: &[ChatCompletionRequestMessage]) -> ChatCompletionRequestMessage {
+    Role::System.message(format!("Summary of {} earlier turns", turns.len()))
+}
+
+fn turns() -> Vec<ChatCompletionRequestMessage> {
+    (0..80)
+        .map(|i| match i % 2 {
+            0 => Role::User.message(format!("Question {}: what does the function on line {} of the file do?", i, i * 3)),
+            _ => Role::Assistant.message(format!("Answer {}: it adds the numbers it is given and returns the sum.", i))
+        })
+        .collect()
+}
+
+fn document(lines: usize) -> String {
+    (0..lines).map(|i| format!("line {} of the document says something\n", i)).collect()
+}
+
+fn main() {
+    let bpe = TiktokenTokenizer::for_model("gpt-4").unwrap();
+    let history = turns();
+
+    // a prompt that fits has all of the history, after the system prompt and out of the json
+    let plan = Chat::builder()
+        .system_prompt("You answer questions about a file.".to_string())
+        .history(history[..4].to_vec())
+        .document(document(5))
+        .build_prompt("gpt-4", 1000, 200)
+        .unwrap();
+
+    let messages = &plan[0].messages;
+    assert_eq!(messages.len(), 6);
+    assert_eq!(messages[0].role, "system");
+    assert_eq!(&messages[1..5], &history[..4]);
+    let user: serde_json::Value = serde_json::from_str(&messages[5].content).unwrap();
+    assert!(user.get("history").is_none());
+
+    // a history that doesn't fit loses its oldest turns, the document stays whole
+    let mut builder = Chat::builder();
+    builder
+        .system_prompt("You answer questions about a file.".to_string())
+        .history(history.clone())
+        .document(document(5));
+
+    let plan = builder.build_prompt("gpt-4", 1000, 200).unwrap();
+    assert_eq!(plan.len(), 1);
+    assert!(plan[0].tokens <= 1000);
+
+    let messages = &plan[0].messages;
+    let kept = messages.len() - 2;
+    assert!(kept > 0 && kept < history.len());
+    assert_eq!(&messages[1..=kept], &history[history.len() - kept..]);
+    assert_eq!(plan[0].sources[0].range, promptize::ChunkRange::Text { bytes: 0..document(5).len(), chars: 0..document(5).len(), lines: 0..5 });
+
+    // a document that has to be chunked takes the budget first
+    let plan = builder.document(document(400)).build_prompt("gpt-4", 1000, 200).unwrap();
+    assert!(plan.len() > 1);
+    for chunk in &plan {
+        assert_eq!(chunk.messages.len(), 2);
+        assert!(chunk.tokens <= 1000);
+    }
+
+    // unless the history has the higher priority, then it keeps as much as the chunkable field can
+    // spare and the same turns go out with every chunk
+    let plan = PinnedChat::builder()
+        .system_prompt("You answer questions about a file.".to_string())
+        .history(history.clone())
+        .document(document(100))
+        .build_prompt("gpt-4", 1000, 200)
+        .unwrap();
+    assert!(plan.len() > 1);
+
+    let kept = plan[0].messages.len() - 2;
+    assert!(kept > 0);
+    for chunk in &plan {
+        assert_eq!(&chunk.messages[1..=kept], &history[history.len() - kept..]);
+        assert!(chunk.tokens <= 1000);
+        assert_eq!(chunk.tokens, bpe.count_messages(&chunk.messages).unwrap());
+    }
+
+    // the turns that are dropped can be summed up instead
+    let plan = SummarizedChat::builder()
+        .system_prompt("You answer questions about a file.".to_string())
+        .history(history.clone())
+        .document(document(5))
+        .build_prompt("gpt-4", 1000, 200)
+        .unwrap();
+    assert_eq!(plan.len(), 1);
+    assert!(plan[0].tokens <= 1000);
+
+    let messages = &plan[0].messages;
+    let kept = messages.len() - 3;
+    let dropped = history.len() - kept;
+    assert_eq!(messages[1].content, format!("Summary of {} earlier turns", dropped));
+    assert_eq!(&messages[2..2 + kept], &history[dropped..]);
+}
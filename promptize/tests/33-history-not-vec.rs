@@ -0,0 +1,14 @@
+// A #[history] field holds chat messages, anything other than a Vec of them is an error.
+
+use promptize::Promptize;
+
+#[derive(Promptize)]
+pub struct Chat {
+    system_prompt: String,
+    #[history]
+    pub history: String,
+    #[chunkable]
+    pub document: String
+}
+
+fn main() {}
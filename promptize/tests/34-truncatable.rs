@@ -0,0 +1,90 @@
+// Fields marked #[truncatable] are cut short, lowest priority first, when the other fields leave
+// too little of the budget for the chunkable ones. The ellipsis goes where the text was cut, and
+// every cut is in the plan's truncations. Only once there is nothing left to cut does
+// build_prompt give up with BudgetTooSmall.
+
+use promptize::{Promptize, PromptizeError, ELLIPSIS};
+
+#[derive(Promptize)]
+pub struct Review {
+    #[truncatable(priority = 1, ellipsis = "head")]
+    system_prompt: String,
+    #[truncatable(ellipsis = "middle")]
+    pub readme: String,
+    #[chunkable(split = "lines")]
+    pub file_content: String
+}
+
+fn main() {
+    let bpe = tiktoken_rs::get_bpe_from_model("gpt-4").unwrap();
+    let readme = (0..300).map(|i| format!("readme line {}\n", i)).collect::<String>();
+    let content = "fn main() {}\n".repeat(1000);
+
+    let mut builder = Review::builder();
+    builder
+        .system_prompt("You are a code reviewer.".to_string())
+        .readme(readme.clone())
+        .file_content(content.clone());
+
+    // the readme alone takes more than the 400 tokens the chunks leave for it
+    let plan = builder.build_prompt("gpt-4", 1000, 600).unwrap();
+    assert!(plan.len() > 1);
+    assert_eq!(plan.truncations.len(), 1);
+
+    let truncation = &plan.truncations[0];
+    assert_eq!(truncation.field, "readme");
+    assert!(truncation.tokens_after < truncation.tokens_before);
+    assert!(truncation.removed.start > 0 && truncation.removed.end < readme.len());
+
+    let cut_readme = format!("{}{}{}", &readme[..truncation.removed.start], ELLIPSIS, &readme[truncation.removed.end..]);
+
+    let mut joined = String::new();
+    for prompt in &plan {
+        assert!(prompt.tokens <= 1000);
+        assert_eq!(prompt.messages[0].content, "You are a code reviewer.");
+
+        let user: serde_json::Value = serde_json::from_str(&prompt.messages[1].content).unwrap();
+        assert_eq!(user["readme"], cut_readme.as_str());
+        joined.push_str(user["file_content"].as_str().unwrap());
+    }
+    assert_eq!(joined, content);
+
+    // with the readme cut out altogether, the system prompt is next
+    let system_prompt = (0..200).map(|i| format!("rule {}. ", i)).collect::<String>();
+    let plan = builder
+        .system_prompt(system_prompt.clone())
+        .build_prompt("gpt-4", 1000, 600)
+        .unwrap();
+
+    let fields = plan.truncations.iter().map(|t| t.field).collect::<Vec<_>>();
+    assert_eq!(fields, ["readme", "system_prompt"]);
+    assert_eq!(plan.truncations[0].removed, 0..readme.len());
+    assert_eq!(plan.truncations[0].tokens_after, 0);
+
+    let system = &plan[0].messages[0].content;
+    assert!(system.starts_with(ELLIPSIS) && system.ends_with("rule 199. "));
+    for prompt in &plan {
+        let tokens: usize = prompt.messages.iter().map(|m| bpe.encode_with_special_tokens(&m.content).len()).sum();
+        assert!(tokens <= 1000);
+    }
+
+    // fields that are cut only lose what the chunkable fields need, not the whole of the limit
+    let plan = builder
+        .system_prompt("You are a code reviewer.".to_string())
+        .file_content(String::new())
+        .build_prompt("gpt-4", 1000, 400)
+        .unwrap();
+    assert_eq!(plan.len(), 1);
+    assert_eq!(plan.truncations.len(), 1);
+    assert!(plan.truncations[0].tokens_after > 900);
+    assert!(plan[0].tokens <= 1000);
+
+    // a prompt that fits is sent as it is
+    let plan = builder.file_content("fn main() {}".to_string()).build_prompt("gpt-4", 8000, 600).unwrap();
+    assert_eq!(plan.len(), 1);
+    assert!(plan.truncations.is_empty());
+
+    // not even the chat format's own tokens can be cut
+    let error = builder.file_content(content).build_prompt("gpt-4", 1000, 999).unwrap_err();
+    assert!(matches!(error, PromptizeError::BudgetTooSmall { .. }));
+}
@@ -0,0 +1,14 @@
+// Only text can be cut short, a #[truncatable] field of any other type is an error.
+
+use promptize::Promptize;
+
+#[derive(Promptize)]
+pub struct FileContent {
+    system_prompt: String,
+    #[truncatable]
+    pub lines: usize,
+    #[chunkable]
+    pub file_content: String
+}
+
+fn main() {}
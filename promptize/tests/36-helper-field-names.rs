@@ -0,0 +1,53 @@
+// The methods the derive generates for itself can't clash with the setters of the fields, so a
+// field can be named like any of them.
+
+use promptize::{ChatCompletionRequestMessage, Promptize, Role};
+
+#[derive(Promptize)]
+#[promptize(annotate_chunks)]
+pub struct Helpers {
+    system_prompt: String,
+    #[history]
+    pub with_history: Vec<ChatCompletionRequestMessage>,
+    #[truncatable]
+    pub render: String,
+    pub role_messages: String,
+    pub truncated: bool,
+    pub whole_ranges: usize,
+    pub for_each_prompt: usize,
+    pub chunk_header: String,
+    pub chunk_span: String,
+    #[chunkable]
+    pub with_chunks: String,
+    #[chunkable]
+    pub chunk_sources: Vec<String>
+}
+
+fn main() {
+    let mut builder = Helpers::builder();
+    builder
+        .system_prompt("You are a code reviewer.".to_string())
+        .with_history(vec![Role::User.message("Hi."), Role::Assistant.message("Hello.")])
+        .render("Render everything.".to_string())
+        .role_messages("user".to_string())
+        .truncated(false)
+        .whole_ranges(1)
+        .for_each_prompt(2)
+        .chunk_header("Header".to_string())
+        .chunk_span("1-2".to_string())
+        .with_chunks("fn main() {}\n".to_string())
+        .chunk_sources(vec!["a".to_string(), "b".to_string()]);
+
+    let plan = builder.build_prompt("gpt-4", 1000, 200).unwrap();
+    assert_eq!(plan.len(), 1);
+    assert_eq!(plan[0].messages.len(), 4);
+
+    let user: serde_json::Value = serde_json::from_str(&plan[0].messages[3].content).unwrap();
+    assert_eq!(user["render"], "Render everything.");
+    assert_eq!(user["for_each_prompt"], 2);
+    assert_eq!(user["chunk_sources"], serde_json::json!(["a", "b"]));
+
+    let plan = builder.with_chunks("fn main() {}\n".repeat(1000)).build_prompt("gpt-4", 1000, 200).unwrap();
+    assert!(plan.len() > 1);
+    assert!(plan[0].messages.last().unwrap().content.starts_with("Part 1 of"));
+}
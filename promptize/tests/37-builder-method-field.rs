@@ -0,0 +1,13 @@
+// A field gets a setter of its own name, which can't be the name of one of the builder's methods.
+
+use promptize::Promptize;
+
+#[derive(Promptize)]
+pub struct FileContent {
+    system_prompt: String,
+    pub estimate: String,
+    #[chunkable]
+    pub file_content: String
+}
+
+fn main() {}
@@ -0,0 +1,41 @@
+// A #[history] field can also be an Option<Vec<ChatCompletionRequestMessage>>. Its setter takes
+// the Vec, and leaving it unset sends no history at all.
+
+use promptize::{ChatCompletionRequestMessage, Promptize, Role};
+
+#[derive(Promptize)]
+pub struct Chat {
+    system_prompt: String,
+    #[history]
+    pub turns: Option<Vec<ChatCompletionRequestMessage>>,
+    #[chunkable(split = "lines")]
+    pub document: String
+}
+
+fn main() {
+    let mut builder = Chat::builder();
+    builder
+        .system_prompt("You answer questions about documents.".to_string())
+        .document("The first line.\nThe second line.\n".to_string());
+
+    let plan = builder.build_prompt("gpt-4", 1000, 200).unwrap();
+    assert_eq!(plan[0].messages.len(), 2);
+
+    let turns = vec![Role::User.message("What is it about?"), Role::Assistant.message("Lines.")];
+    let plan = builder.turns(turns.clone()).build_prompt("gpt-4", 1000, 200).unwrap();
+
+    let messages = &plan[0].messages;
+    assert_eq!(messages.len(), 4);
+    assert_eq!(messages[1].content, turns[0].content);
+    assert_eq!(messages[2].content, turns[1].content);
+
+    // chunked, the document needs all of the budget and the history is left out to make room
+    let plan = builder
+        .document("A line of the document.\n".repeat(2000))
+        .build_prompt("gpt-4", 1000, 200)
+        .unwrap();
+    assert!(plan.len() > 1);
+    for prompt in &plan {
+        assert_eq!(prompt.messages.len(), 2);
+    }
+}
@@ -0,0 +1,14 @@
+// The chunks of a chunkable field go in the user message along with the other fields, so a
+// chunkable field can't also be sent as a message of its own with #[role(...)].
+
+use promptize::Promptize;
+
+#[derive(Promptize)]
+pub struct FileContent {
+    system_prompt: String,
+    #[role(user)]
+    #[chunkable]
+    pub file_content: String
+}
+
+fn main() {}
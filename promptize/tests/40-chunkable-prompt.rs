@@ -0,0 +1,13 @@
+// system_prompt and user_prompt are always sent as messages of their own, so neither can be
+// chunkable either.
+
+use promptize::Promptize;
+
+#[derive(Promptize)]
+pub struct FileContent {
+    #[chunkable]
+    system_prompt: String,
+    pub file_name: String
+}
+
+fn main() {}
@@ -0,0 +1,45 @@
+// #[promptize(template_file = "...")] reads the template from a file instead. The path is relative
+// to the crate root, or when there is no such file there, to the file the struct is in, like an
+// include_str! would be. The template is checked just like one written inline.
+
+use promptize::Promptize;
+
+#[derive(Promptize)]
+#[promptize(template_file = "fixtures/review-template.txt")]
+pub struct FileContent {
+    system_prompt: String,
+    pub filename: String,
+    #[chunkable(split = "lines")]
+    pub file_content: String
+}
+
+fn main() {
+    let template = include_str!("fixtures/review-template.txt");
+
+    let mut builder = FileContent::builder();
+    builder
+        .system_prompt("You are a code reviewer.".to_string())
+        .filename("main.rs".to_string())
+        .file_content("fn main() {}".to_string());
+
+    let prompts = builder.build_prompt("gpt-4", 1000, 200).unwrap();
+    let expected = template
+        .replace("{filename}", "main.rs")
+        .replace("{file_content}", "fn main() {}")
+        .replace("{{path}}", "{path}");
+    assert_eq!(prompts[0].messages[1].content, expected);
+
+    let content = "fn main() {}\n".repeat(2000);
+    let prompts = builder.file_content(content.clone()).build_prompt("gpt-4", 1000, 200).unwrap();
+    assert!(prompts.len() > 1);
+
+    let mut joined = String::new();
+    for prompt in &prompts {
+        let chunk = prompt.messages[1].content
+            .strip_prefix("Review main.rs, {path} is a placeholder of the reviewer's own:\n")
+            .and_then(|rest| rest.strip_suffix('\n'))
+            .unwrap();
+        joined.push_str(chunk);
+    }
+    assert_eq!(joined, content);
+}
@@ -2,6 +2,45 @@
 fn tests() {
     let t = trybuild::TestCases::new();
     t.pass("tests/01-parse.rs");
+    t.pass("tests/03-token-boundaries.rs");
+    t.pass("tests/04-repeated-fields.rs");
+    t.pass("tests/05-overlap.rs");
+    t.pass("tests/06-split-lines.rs");
+    t.pass("tests/07-split-rust-items.rs");
+    t.compile_fail("tests/08-unrecognized-split.rs");
+    t.pass("tests/09-errors.rs");
+    t.pass("tests/10-default.rs");
+    t.pass("tests/11-roles.rs");
+    t.pass("tests/12-single-request.rs");
+    t.pass("tests/13-template.rs");
+    t.compile_fail("tests/14-template-unknown-field.rs");
+    t.pass("tests/15-multiple-chunkable.rs");
+    t.pass("tests/16-cartesian-layout.rs");
+    t.compile_fail("tests/17-unrecognized-layout.rs");
+    t.pass("tests/18-chunkable-vec.rs");
+    t.pass("tests/19-chunkable-vec-overlap.rs");
+    t.pass("tests/20-chunkable-str.rs");
+    t.compile_fail("tests/21-chunkable-unsupported-type.rs");
+    t.pass("tests/22-tokenizer.rs");
+    t.pass("tests/23-shared-encoder.rs");
+    t.pass("tests/24-model-registry.rs");
+    t.pass("tests/25-message-overhead.rs");
+    t.pass("tests/26-prompt-plan.rs");
+    t.pass("tests/27-reduce-prompt.rs");
+    t.pass("tests/28-annotate-chunks.rs");
+    t.pass("tests/29-multiple-derives.rs");
+    t.pass("tests/30-estimate.rs");
+    t.pass("tests/31-response-schema.rs");
+    t.pass("tests/32-history.rs");
+    t.compile_fail("tests/33-history-not-vec.rs");
+    t.pass("tests/34-truncatable.rs");
+    t.compile_fail("tests/35-truncatable-not-string.rs");
+    t.pass("tests/36-helper-field-names.rs");
+    t.compile_fail("tests/37-builder-method-field.rs");
+    t.pass("tests/38-optional-history.rs");
+    t.compile_fail("tests/39-chunkable-role.rs");
+    t.compile_fail("tests/40-chunkable-prompt.rs");
+    t.pass("tests/41-template-file.rs");
     // t.pass("tests/02-chunker.rs");
     // t.pass("tests/03-call-setters.rs");
     // t.pass("tests/04-call-build.rs");